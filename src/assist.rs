@@ -0,0 +1,268 @@
+use nalgebra::{Vector2, Isometry2, Rotation2};
+use alga::linear::Transformation;
+
+use weapons::wrap_angle;
+use controllers::Input;
+
+// How hard the spin and velocity controllers push back on how far off they are, on how long they've been off and on how fast that's changing
+const SPIN_GAINS: Gains = Gains { proportional: 0.3, integral: 0.02, derivative: 0.1 };
+const VELOCITY_GAINS: Gains = Gains { proportional: 0.2, integral: 0.01, derivative: 0.05 };
+// How much of what was built up over earlier ticks the integral keeps each tick, so it can't wind up without end
+const INTEGRAL_DECAY: f32 = 0.9;
+// How fast to spin towards the aim for each radian the nose is off it, and the fastest it spins there
+const POINT_RATE: f32 = 3.0;
+const MAX_POINT_SPIN: f32 = 4.0;
+
+// What flight assist does when the pilot isn't steering or thrusting themselves. Each mode does what the one before it does too,
+// so holding velocity also stops the ship spinning, and pointing at the aim also holds velocity
+#[derive(Clone, Copy, PartialEq)]
+pub enum Assist {
+    Off,
+    // Stop the ship spinning
+    KillRotation,
+    // Keep the ship moving at the velocity it had when the pilot stopped thrusting
+    HoldVelocity,
+    // Turn the nose towards where the first weapon group aims, which is the mouse for the player using it,
+    // or along the aim direction for a player on a stick
+    PointAtAim
+}
+
+impl Assist {
+    // The next mode round, for a key that cycles through them
+    pub fn next(&self) -> Self {
+        match *self {
+            Assist::Off => Assist::KillRotation,
+            Assist::KillRotation => Assist::HoldVelocity,
+            Assist::HoldVelocity => Assist::PointAtAim,
+            Assist::PointAtAim => Assist::Off
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Assist::Off => "off",
+            Assist::KillRotation => "rotation",
+            Assist::HoldVelocity => "velocity",
+            Assist::PointAtAim => "point"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(Assist::Off),
+            "rotation" => Some(Assist::KillRotation),
+            "velocity" => Some(Assist::HoldVelocity),
+            "point" => Some(Assist::PointAtAim),
+            _ => None
+        }
+    }
+
+    // How far on from off the mode is, which is also how much it does
+    pub fn level(&self) -> usize {
+        match *self {
+            Assist::Off => 0,
+            Assist::KillRotation => 1,
+            Assist::HoldVelocity => 2,
+            Assist::PointAtAim => 3
+        }
+    }
+
+    fn holds_velocity(&self) -> bool {
+        *self == Assist::HoldVelocity || *self == Assist::PointAtAim
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Gains {
+    proportional: f32,
+    integral: f32,
+    derivative: f32
+}
+
+// A PID controller, which works out a correction each tick from how far something is from where it should be
+#[derive(Clone)]
+struct Pid {
+    gains: Gains,
+    integral: f32,
+    // How far off it was last tick, or None if it has only just started
+    previous: Option<f32>
+}
+
+impl Pid {
+    fn new(gains: Gains) -> Self {
+        Self {
+            gains,
+            integral: 0.0,
+            previous: None
+        }
+    }
+
+    fn step(&mut self, error: f32) -> f32 {
+        self.integral = self.integral * INTEGRAL_DECAY + error;
+        let derivative = error - self.previous.unwrap_or(error);
+        self.previous = Some(error);
+
+        self.gains.proportional * error + self.gains.integral * self.integral + self.gains.derivative * derivative
+    }
+
+    // Forget everything, for when the controller stops being used, so it starts afresh next time
+    fn reset(&mut self) {
+        self.integral = 0.0;
+        self.previous = None;
+    }
+}
+
+// A ship's flight assist, which keeps what its controllers have built up from tick to tick
+#[derive(Clone)]
+pub struct FlightAssist {
+    spin: Pid,
+    velocity: (Pid, Pid),
+    // The velocity being held, from when the pilot last stopped thrusting
+    held: Option<Vector2<f32>>
+}
+
+impl Default for FlightAssist {
+    fn default() -> Self {
+        Self {
+            spin: Pid::new(SPIN_GAINS),
+            velocity: (Pid::new(VELOCITY_GAINS), Pid::new(VELOCITY_GAINS)),
+            held: None
+        }
+    }
+}
+
+impl FlightAssist {
+    // Take over whatever the pilot isn't doing themselves. Takes the change in velocity, in the ship's own frame, and the change in spin
+    // the pilot asked for this tick along with how the ship is moving, and returns those with the assist's corrections in their place
+    pub fn step(&mut self, input: &Input, position: &Isometry2<f32>, lin_vel: Vector2<f32>, ang_vel: f32, linear: Vector2<f32>, angular: f32)
+        -> (Vector2<f32>, f32) {
+        let mode = input.assist;
+        let rotation = position.rotation.arg();
+
+        let angular = if mode == Assist::Off || input.left || input.right {
+            self.spin.reset();
+            angular
+        } else {
+            let target = if mode == Assist::PointAtAim {
+                let towards = match input.aim_direction {
+                    Some((x, y)) => Vector2::new(x, y),
+                    None => Vector2::new(input.aims[0].0, input.aims[0].1) - position.translation.vector
+                };
+
+                // Ships face up, so a heading of zero points along negative y
+                let error = wrap_angle(towards.x.atan2(-towards.y) - rotation);
+                (error * POINT_RATE).max(-MAX_POINT_SPIN).min(MAX_POINT_SPIN)
+            } else {
+                0.0
+            };
+
+            self.spin.step(target - ang_vel)
+        };
+
+        let linear = if !mode.holds_velocity() || input.up {
+            self.velocity.0.reset();
+            self.velocity.1.reset();
+            self.held = None;
+            linear
+        } else {
+            let held = *self.held.get_or_insert(lin_vel);
+            let error = held - lin_vel;
+            let correction = Vector2::new(self.velocity.0.step(error.x), self.velocity.1.step(error.y));
+
+            // The engines want it in the ship's own frame
+            Rotation2::new(-rotation).transform_vector(&correction)
+        };
+
+        (linear, angular)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(assist: Assist) -> Input {
+        Input {
+            assist,
+            ..Input::default()
+        }
+    }
+
+    // Step the assist for a ship at the origin pointing up, which isn't asking for anything itself
+    fn step(assist: &mut FlightAssist, input: &Input, lin_vel: Vector2<f32>, ang_vel: f32) -> (Vector2<f32>, f32) {
+        assist.step(input, &Isometry2::new(Vector2::new(0.0, 0.0), 0.0), lin_vel, ang_vel, Vector2::new(0.0, 0.0), 0.0)
+    }
+
+    #[test]
+    fn off_leaves_the_pilot_alone() {
+        let mut assist = FlightAssist::default();
+        let position = Isometry2::new(Vector2::new(0.0, 0.0), 0.0);
+        let (linear, angular) = assist.step(&input(Assist::Off), &position, Vector2::new(3.0, 0.0), 1.0, Vector2::new(0.0, -1.0), 0.5);
+
+        assert!((linear - Vector2::new(0.0, -1.0)).norm() < 1e-6);
+        assert!((angular - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn kill_rotation_pushes_against_the_spin() {
+        let mut assist = FlightAssist::default();
+        let (_, angular) = step(&mut assist, &input(Assist::KillRotation), Vector2::new(0.0, 0.0), 1.0);
+
+        // Only the proportional and integral terms count on the first tick
+        assert!((angular + SPIN_GAINS.proportional + SPIN_GAINS.integral).abs() < 1e-6);
+    }
+
+    #[test]
+    fn steering_takes_over_from_kill_rotation() {
+        let mut assist = FlightAssist::default();
+        let mut input = input(Assist::KillRotation);
+        input.left = true;
+
+        let position = Isometry2::new(Vector2::new(0.0, 0.0), 0.0);
+        let (_, angular) = assist.step(&input, &position, Vector2::new(0.0, 0.0), 1.0, Vector2::new(0.0, 0.0), -0.7);
+
+        assert!((angular + 0.7).abs() < 1e-6);
+    }
+
+    #[test]
+    fn hold_velocity_pulls_back_to_what_it_held() {
+        let mut assist = FlightAssist::default();
+        let input = input(Assist::HoldVelocity);
+
+        let (linear, _) = step(&mut assist, &input, Vector2::new(0.0, 0.0), 0.0);
+        assert_eq!(linear.norm(), 0.0);
+
+        // Drifting off by one, with the derivative seeing it happen all at once
+        let (linear, _) = step(&mut assist, &input, Vector2::new(1.0, 0.0), 0.0);
+        let expected = -(VELOCITY_GAINS.proportional + VELOCITY_GAINS.integral + VELOCITY_GAINS.derivative);
+        assert!((linear - Vector2::new(expected, 0.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn thrusting_lets_go_of_the_held_velocity() {
+        let mut assist = FlightAssist::default();
+        let mut input = input(Assist::HoldVelocity);
+        step(&mut assist, &input, Vector2::new(0.0, 0.0), 0.0);
+
+        input.up = true;
+        let position = Isometry2::new(Vector2::new(0.0, 0.0), 0.0);
+        let (linear, _) = assist.step(&input, &position, Vector2::new(0.0, 0.0), 0.0, Vector2::new(0.0, -1.0), 0.0);
+        assert!((linear - Vector2::new(0.0, -1.0)).norm() < 1e-6);
+
+        // Letting go holds the new velocity rather than pulling back to the old one
+        input.up = false;
+        let (linear, _) = step(&mut assist, &input, Vector2::new(0.0, -5.0), 0.0);
+        assert_eq!(linear.norm(), 0.0);
+    }
+
+    #[test]
+    fn point_at_aim_turns_towards_it_no_faster_than_its_limit() {
+        let mut assist = FlightAssist::default();
+        let mut input = input(Assist::PointAtAim);
+        input.aim_direction = Some((1.0, 0.0));
+
+        // A quarter turn off is more than the spin limit
+        let (_, angular) = step(&mut assist, &input, Vector2::new(0.0, 0.0), 0.0);
+        assert!((angular - MAX_POINT_SPIN * (SPIN_GAINS.proportional + SPIN_GAINS.integral)).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,129 @@
+use rand::Rng;
+use nalgebra::{Vector1, Vector2};
+
+use std::f32::consts::PI;
+
+use context::Context;
+use ships::{Ship, Component, ComponentType, Rotation};
+use random::CountingRng;
+
+// The grid offsets of a square's neighbours
+const NEIGHBOURS: [(i8, i8); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+// Generates asteroids from a seed and keeps the area around a point stocked with them
+pub struct AsteroidField {
+    seed: u32,
+    rng: CountingRng,
+    // How many asteroids to keep within the radius
+    pub count: usize,
+    pub radius: f32,
+    // The smallest and largest asteroids, in components
+    pub min_size: usize,
+    pub max_size: usize,
+    // From 0 to 1, where dense asteroids are compact lumps and sparse ones are stringy
+    pub density: f32,
+    // The fastest asteroids drift and spin
+    pub max_drift: f32,
+    pub max_spin: f32
+}
+
+impl AsteroidField {
+    pub fn new(seed: u32) -> Self {
+        Self::resume(seed, 0)
+    }
+
+    // A field carrying on from a saved one, whose generator had drawn some numbers from the seed
+    pub fn resume(seed: u32, draws: u64) -> Self {
+        Self {
+            seed,
+            // Xorshift can't be seeded with all zeroes
+            rng: CountingRng::resume([seed, 0x2545_f491, 0x9e37_79b9, 0x85eb_ca6b], draws),
+            count: 12,
+            radius: 1500.0,
+            min_size: 3,
+            max_size: 14,
+            density: 0.6,
+            max_drift: 40.0,
+            max_spin: 1.0
+        }
+    }
+
+    // The seed and how many numbers have been drawn from it, for a saved game to carry on generating from
+    pub fn seed(&self) -> (u32, u64) {
+        (self.seed, self.rng.draws())
+    }
+
+    // Grow an irregular clump of rock outwards from a single square
+    pub fn generate(&mut self) -> Vec<Component> {
+        let size = self.rng.gen_range(self.min_size, self.max_size + 1);
+        let mut squares = vec![(0i8, 0i8)];
+
+        while squares.len() < size {
+            let (x, y) = squares[self.rng.gen_range(0, squares.len())];
+            let (dx, dy) = NEIGHBOURS[self.rng.gen_range(0, NEIGHBOURS.len())];
+            let square = (x + dx, y + dy);
+
+            if squares.contains(&square) {
+                continue;
+            }
+
+            // Dense asteroids prefer to fill in gaps that already have rock on several sides
+            let neighbours = NEIGHBOURS.iter().filter(|&&(dx, dy)| squares.contains(&(square.0 + dx, square.1 + dy))).count();
+            let chance = 1.0 - self.density + self.density * neighbours as f32 / NEIGHBOURS.len() as f32;
+
+            if self.rng.gen::<f32>() < chance {
+                squares.push(square);
+            }
+        }
+
+        squares.into_iter()
+            .map(|(x, y)| {
+                let rotation = match self.rng.gen_range(0, 4) {
+                    0 => Rotation::Up,
+                    1 => Rotation::Right,
+                    2 => Rotation::Down,
+                    _ => Rotation::Left
+                };
+
+                Component::new(ComponentType::Rock, x, y, rotation)
+            })
+            .collect()
+    }
+
+    // Remove asteroids that have drifted far away, and spawn new ones at least min_distance from the centre until there are enough
+    pub fn populate(&mut self, ctx: &mut Context, ships: &mut Vec<Ship>, (x, y): (f32, f32), min_distance: f32) {
+        let distance = |ship: &Ship| {
+            let (ship_x, ship_y) = ship.position();
+            (ship_x - x).hypot(ship_y - y)
+        };
+
+        let despawn = self.radius * 2.0;
+
+        for ship in ships.iter().filter(|ship| ship.is_asteroid() && distance(ship) > despawn) {
+            ctx.remove_rigid_body(&ship.handle);
+        }
+
+        ships.retain(|ship| !ship.is_asteroid() || distance(ship) <= despawn);
+
+        let nearby = ships.iter().filter(|ship| ship.is_asteroid() && distance(ship) <= self.radius).count();
+
+        for _ in nearby .. self.count {
+            let angle = self.rng.gen_range(0.0, 2.0 * PI);
+            let offset = self.rng.gen_range(min_distance, self.radius);
+            let rotation = self.rng.gen_range(0.0, 2.0 * PI);
+            let components = self.generate();
+
+            let ship = Ship::new(ctx, components, x + angle.cos() * offset, y + angle.sin() * offset, rotation);
+
+            {
+                let mut rigid_body = ship.handle.borrow_mut();
+                let drift = self.rng.gen_range(0.0, 2.0 * PI);
+                let speed = self.rng.gen_range(0.0, self.max_drift);
+                rigid_body.set_lin_vel(Vector2::new(drift.cos(), drift.sin()) * speed);
+                rigid_body.set_ang_vel(Vector1::new(self.rng.gen_range(-self.max_spin, self.max_spin)));
+            }
+
+            ships.push(ship);
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use ships::{Component, ComponentType, Rotation};
+use weapons::WeaponType;
+
+// Blueprints are a grid of two character cells separated by whitespace.
+// The first character is the component and the second is its rotation, with `..` for an empty cell:
+//
+//   M Metal    P Pipe    R Reactor    E Engine    O Rock    A Magazine    K Collector
+//   L Laser hardpoint    T Traction beam hardpoint    C Cannon hardpoint
+//   X Missile hardpoint  D Mass driver hardpoint
+//
+//   ^ Up    > Right    v Down    < Left
+//
+// Lines starting with `#` are comments, which are kept when the blueprint is saved but all moved to the top,
+// and there are two optional directives:
+//
+//   origin <column> <row>   The cell that sits at 0, 0 on the ship (defaults to the top left)
+//   spawn <x> <y> <angle>   Where the ship is placed in the world (defaults to 0 0 0)
+
+const EMPTY: &str = "..";
+
+#[derive(Debug)]
+pub struct BlueprintError {
+    pub line: usize,
+    pub message: String
+}
+
+impl BlueprintError {
+    fn new(line: usize, message: String) -> Self {
+        Self {
+            line, message
+        }
+    }
+}
+
+impl fmt::Display for BlueprintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for BlueprintError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+fn parse_tag(symbol: char) -> Option<ComponentType> {
+    Some(match symbol {
+        'M' => ComponentType::Metal,
+        'P' => ComponentType::Pipe,
+        'R' => ComponentType::Reactor,
+        'E' => ComponentType::Engine,
+        'O' => ComponentType::Rock,
+        'A' => ComponentType::magazine(),
+        'K' => ComponentType::collector(),
+        'L' => ComponentType::hardpoint(WeaponType::Laser),
+        'T' => ComponentType::hardpoint(WeaponType::TractionBeam),
+        'C' => ComponentType::hardpoint(WeaponType::Cannon),
+        'X' => ComponentType::hardpoint(WeaponType::Missile),
+        'D' => ComponentType::hardpoint(WeaponType::MassDriver),
+        _ => return None
+    })
+}
+
+fn tag_symbol(tag: &ComponentType) -> char {
+    match *tag {
+        ComponentType::Metal => 'M',
+        ComponentType::Pipe => 'P',
+        ComponentType::Reactor => 'R',
+        ComponentType::Engine => 'E',
+        ComponentType::Rock => 'O',
+        ComponentType::Magazine(_) => 'A',
+        ComponentType::Collector(_) => 'K',
+        ComponentType::Hardpoint(ref weapon) => match weapon.tag() {
+            WeaponType::Laser => 'L',
+            WeaponType::TractionBeam => 'T',
+            WeaponType::Cannon => 'C',
+            WeaponType::Missile => 'X',
+            WeaponType::MassDriver => 'D'
+        }
+    }
+}
+
+fn parse_rotation(symbol: char) -> Option<Rotation> {
+    Some(match symbol {
+        '^' => Rotation::Up,
+        '>' => Rotation::Right,
+        'v' => Rotation::Down,
+        '<' => Rotation::Left,
+        _ => return None
+    })
+}
+
+fn rotation_symbol(rotation: &Rotation) -> char {
+    match *rotation {
+        Rotation::Up => '^',
+        Rotation::Right => '>',
+        Rotation::Down => 'v',
+        Rotation::Left => '<'
+    }
+}
+
+// Parse a single cell into a component type and rotation
+pub fn parse_cell(cell: &str) -> Result<(ComponentType, Rotation), String> {
+    let mut chars = cell.chars();
+
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(tag), Some(rotation), None) => Ok((
+            parse_tag(tag).ok_or_else(|| format!("unknown component '{}' in '{}'", tag, cell))?,
+            parse_rotation(rotation).ok_or_else(|| format!("unknown rotation '{}' in '{}'", rotation, cell))?
+        )),
+        _ => Err(format!("expected a two character cell, got '{}'", cell))
+    }
+}
+
+// Write a component type and rotation as a single cell
+pub fn cell(tag: &ComponentType, rotation: &Rotation) -> String {
+    format!("{}{}", tag_symbol(tag), rotation_symbol(rotation))
+}
+
+// Parse the arguments of a directive as numbers
+fn parse_numbers<T: ::std::str::FromStr>(line: usize, directive: &str, args: &[&str], count: usize) -> Result<Vec<T>, BlueprintError> {
+    if args.len() != count {
+        return Err(BlueprintError::new(line, format!("'{}' takes {} numbers, got {}", directive, count, args.len())));
+    }
+
+    args.iter()
+        .map(|arg| arg.parse().map_err(|_| BlueprintError::new(line, format!("'{}' is not a valid number for '{}'", arg, directive))))
+        .collect()
+}
+
+pub struct Blueprint {
+    pub components: Vec<Component>,
+    pub spawn: (f32, f32, f32),
+    // The comment lines, so that saving a blueprint over its file doesn't lose them
+    pub comments: Vec<String>
+}
+
+impl Blueprint {
+    pub fn new(components: Vec<Component>, spawn: (f32, f32, f32)) -> Self {
+        Self {
+            components, spawn,
+            comments: Vec::new()
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, BlueprintError> {
+        let mut origin = (0, 0);
+        let mut spawn = (0.0, 0.0, 0.0);
+        let mut comments = Vec::new();
+        // Cells along with their column, row and line number
+        let mut cells = Vec::new();
+        let mut row = 0;
+
+        for (i, line) in text.lines().enumerate() {
+            let number = i + 1;
+            let line = line.trim();
+
+            if line.starts_with('#') {
+                comments.push(line.to_string());
+                continue;
+            }
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+
+            match words[0] {
+                "origin" => {
+                    let values = parse_numbers::<i32>(number, words[0], &words[1..], 2)?;
+                    origin = (values[0], values[1]);
+                },
+                "spawn" => {
+                    let values = parse_numbers::<f32>(number, words[0], &words[1..], 3)?;
+                    spawn = (values[0], values[1], values[2]);
+                },
+                _ => {
+                    for (column, word) in words.iter().enumerate() {
+                        if *word != EMPTY {
+                            let (tag, rotation) = parse_cell(word).map_err(|message| BlueprintError::new(number, message))?;
+                            cells.push((tag, rotation, column as i32, row, number));
+                        }
+                    }
+
+                    row += 1;
+                }
+            }
+        }
+
+        if cells.is_empty() {
+            return Err(BlueprintError::new(text.lines().count(), "blueprint has no components".into()));
+        }
+
+        let components = cells.into_iter()
+            .map(|(tag, rotation, column, row, number)| {
+                let (x, y) = (column - origin.0, row - origin.1);
+
+                if x < i32::from(i8::min_value()) || x > i32::from(i8::max_value()) || y < i32::from(i8::min_value()) || y > i32::from(i8::max_value()) {
+                    Err(BlueprintError::new(number, format!("component at {}, {} is too far from the origin", x, y)))
+                } else {
+                    Ok(Component::new(tag, x as i8, y as i8, rotation))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            components, spawn, comments
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<Error>> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Self::parse(&text).map_err(|err| format!("{}: {}", path.display(), err).into())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<Error>> {
+        File::create(path)?.write_all(self.to_string().as_bytes())?;
+        Ok(())
+    }
+
+    // Load every blueprint in a directory, sorted by name
+    pub fn load_dir(dir: &Path) -> Result<Vec<(String, Self)>, Box<Error>> {
+        let mut blueprints = Vec::new();
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.extension().map_or(false, |extension| extension == "ship") {
+                let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+                blueprints.push((name, Self::load(&path)?));
+            }
+        }
+
+        blueprints.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(blueprints)
+    }
+}
+
+impl fmt::Display for Blueprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let min_x = self.components.iter().map(|component| component.x).min().unwrap_or(0);
+        let max_x = self.components.iter().map(|component| component.x).max().unwrap_or(0);
+        let min_y = self.components.iter().map(|component| component.y).min().unwrap_or(0);
+        let max_y = self.components.iter().map(|component| component.y).max().unwrap_or(0);
+
+        for comment in &self.comments {
+            writeln!(f, "{}", comment)?;
+        }
+
+        writeln!(f, "spawn {} {} {}", self.spawn.0, self.spawn.1, self.spawn.2)?;
+        writeln!(f, "origin {} {}", -i32::from(min_x), -i32::from(min_y))?;
+        writeln!(f)?;
+
+        for y in min_y .. max_y + 1 {
+            let row = (min_x .. max_x + 1)
+                .map(|x| self.components.iter()
+                    .find(|component| component.x == x && component.y == y)
+                    .map_or(EMPTY.into(), |component| cell(&component.tag, &component.rotation)))
+                .collect::<Vec<_>>();
+
+            writeln!(f, "{}", row.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let blueprint = Blueprint::parse(include_str!("../ships/player.ship")).unwrap();
+        let text = blueprint.to_string();
+        let parsed = Blueprint::parse(&text).unwrap();
+
+        assert_eq!(parsed.components.len(), blueprint.components.len());
+        assert_eq!(parsed.spawn, blueprint.spawn);
+        assert_eq!(parsed.comments, blueprint.comments);
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn comments_are_kept() {
+        let blueprint = Blueprint::parse("# A ship\nM^ R>\n# Its engine\n.. E^").unwrap();
+
+        assert_eq!(blueprint.comments, vec!["# A ship", "# Its engine"]);
+        assert!(blueprint.to_string().starts_with("# A ship\n# Its engine\n"));
+    }
+
+    #[test]
+    fn origin_moves_the_grid() {
+        let blueprint = Blueprint::parse("origin 1 1\nM^ ..\n.. R>").unwrap();
+        let positions = blueprint.components.iter().map(|component| (component.x, component.y)).collect::<Vec<_>>();
+
+        assert_eq!(positions, vec![(-1, -1), (0, 0)]);
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+        assert_eq!(Blueprint::parse("# comment\nM^ Q^").unwrap_err().line, 2);
+        assert_eq!(Blueprint::parse("spawn 1 2\nM^").unwrap_err().line, 1);
+        assert!(Blueprint::parse("# nothing\n.. ..").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use rand::{self, Rng};
+
+// How quickly the camera catches up with its target and zoom, per second
+const FOLLOW_RATE: f32 = 4.0;
+const ZOOM_RATE: f32 = 8.0;
+// How much each notch of the mouse wheel zooms by, and the limits of zooming
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.0;
+// How quickly screen shake dies away, per second
+const SHAKE_DECAY: f32 = 6.0;
+
+pub struct Camera {
+    // The centre of the view in world coordinates
+    x: f32,
+    y: f32,
+    zoom: f32,
+    target_zoom: f32,
+    // The most the camera can zoom in and still fit everything it has been asked to
+    fit_zoom: f32,
+    // The size of the view in pixels
+    width: f32,
+    height: f32,
+    // How far the view is being shaken, and the current offset from shaking
+    shake: f32,
+    offset: (f32, f32)
+}
+
+impl Camera {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            x: width / 2.0,
+            y: height / 2.0,
+            zoom: 1.0,
+            target_zoom: 1.0,
+            fit_zoom: MAX_ZOOM,
+            width, height,
+            shake: 0.0,
+            offset: (0.0, 0.0)
+        }
+    }
+
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    // Zoom in or out by a number of mouse wheel notches
+    pub fn zoom_by(&mut self, notches: i32) {
+        self.target_zoom = (self.target_zoom * ZOOM_STEP.powi(notches)).max(MIN_ZOOM).min(MAX_ZOOM);
+    }
+
+    // Keep an area of the world in view, zooming out past the chosen zoom if it has to. An empty area leaves the zoom alone
+    pub fn fit(&mut self, width: f32, height: f32) {
+        self.fit_zoom = (self.width / width).min(self.height / height).max(MIN_ZOOM).min(MAX_ZOOM);
+    }
+
+    pub fn shake(&mut self, amount: f32) {
+        self.shake = self.shake.max(amount);
+    }
+
+    // Move smoothly towards a point and the target zoom, and jiggle the view if it's shaking
+    pub fn step(&mut self, (x, y): (f32, f32), dt: f32) {
+        let follow = 1.0 - (-FOLLOW_RATE * dt).exp();
+        self.x += (x - self.x) * follow;
+        self.y += (y - self.y) * follow;
+
+        let zoom = 1.0 - (-ZOOM_RATE * dt).exp();
+        self.zoom += (self.target_zoom.min(self.fit_zoom) - self.zoom) * zoom;
+
+        self.shake *= (-SHAKE_DECAY * dt).exp();
+
+        self.offset = if self.shake > 0.1 {
+            let mut rng = rand::thread_rng();
+            (rng.gen_range(-self.shake, self.shake), rng.gen_range(-self.shake, self.shake))
+        } else {
+            (0.0, 0.0)
+        };
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    // The point in the world the view is centred on before any shaking
+    pub fn centre(&self) -> (f32, f32) {
+        (self.x, self.y)
+    }
+
+    // The point in the world at the middle of the view, shaking included. The offset is in pixels on the screen,
+    // so it's scaled down by the zoom and goes the other way to the world it moves
+    pub fn position(&self) -> (f32, f32) {
+        (self.x - self.offset.0 / self.zoom, self.y - self.offset.1 / self.zoom)
+    }
+
+    pub fn size(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+
+    pub fn world_to_screen(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            (x - self.x) * self.zoom + self.width / 2.0 + self.offset.0,
+            (y - self.y) * self.zoom + self.height / 2.0 + self.offset.1
+        )
+    }
+
+    pub fn screen_to_world(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            (x - self.width / 2.0 - self.offset.0) / self.zoom + self.x,
+            (y - self.height / 2.0 - self.offset.1) / self.zoom + self.y
+        )
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::Instant;
+
+// The longest frame the clock will catch up on, so a stall doesn't turn into a burst of ticks
+const MAX_FRAME: f32 = 0.25;
+// How much each press slows down or speeds up time, and how far it can go
+const SCALE_STEP: f32 = 2.0;
+const MIN_SCALE: f32 = 0.125;
+const MAX_SCALE: f32 = 4.0;
+
+// Turns real time into a whole number of fixed length ticks, which can be paused, stepped through one at a time and sped up or slowed down
+pub struct Clock {
+    dt: f32,
+    last: Instant,
+    // Scaled time that hasn't been used up by a tick yet
+    accumulator: f32,
+    // Real time between the last two frames
+    frame: f32,
+    scale: f32,
+    paused: bool,
+    // Whether to run a single tick while paused
+    step: bool
+}
+
+impl Clock {
+    pub fn new(dt: f32) -> Self {
+        Self {
+            dt,
+            last: Instant::now(),
+            accumulator: 0.0,
+            frame: 0.0,
+            scale: 1.0,
+            paused: false,
+            step: false
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // Run one tick on the next frame, pausing if the clock is running
+    pub fn step_once(&mut self) {
+        self.paused = true;
+        self.step = true;
+    }
+
+    pub fn slow_down(&mut self) {
+        self.scale = (self.scale / SCALE_STEP).max(MIN_SCALE);
+    }
+
+    pub fn speed_up(&mut self) {
+        self.scale = (self.scale * SCALE_STEP).min(MAX_SCALE);
+    }
+
+    // How many ticks to run for the time that has passed since the last frame
+    pub fn ticks(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.last = now;
+        self.frame = (elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 * 1e-9).min(MAX_FRAME);
+
+        if self.paused {
+            let step = self.step;
+            self.step = false;
+            return if step { 1 } else { 0 };
+        }
+
+        self.accumulator += self.frame * self.scale;
+        let ticks = (self.accumulator / self.dt) as u32;
+        self.accumulator -= ticks as f32 * self.dt;
+        ticks
+    }
+
+    // How far between the last tick and the next one the current frame falls
+    pub fn alpha(&self) -> f32 {
+        if self.paused {
+            1.0
+        } else {
+            self.accumulator / self.dt
+        }
+    }
+
+    // The real time the last frame took, in seconds
+    pub fn frame_time(&self) -> f32 {
+        self.frame
+    }
+}
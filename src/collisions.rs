@@ -0,0 +1,137 @@
+use nalgebra::{Point2, Vector2};
+
+use std::rc::Rc;
+
+use context::Context;
+use ships::{Ship, Motion};
+use pickups::Pickups;
+use weapons::Damage;
+
+// How fast two ships have to meet before it does any harm, and how much damage each unit of speed past that does
+const MIN_IMPACT_SPEED: f32 = 60.0;
+const DAMAGE_PER_SPEED: f32 = 0.4;
+
+// How much damage two ships meeting at a point do, going by how fast they're closing along the normal there, which points
+// from the first to the second
+fn impact(first: &Motion, second: &Motion, point: Point2<f32>, normal: Vector2<f32>) -> f32 {
+    let speed = (first.velocity_at(point.coords) - second.velocity_at(point.coords)).dot(&normal);
+    (speed - MIN_IMPACT_SPEED).max(0.0) * DAMAGE_PER_SPEED
+}
+
+// Damage ships that have run into each other in the last physics step, going by how fast they were closing along the
+// contact normal before the step pushed them apart, so ships scraping past each other don't take the damage of a head-on
+// crash. Each side takes damage to the part nearest the contact, with denser parts hitting harder, and armour taking it
+// as it would a shot that can't penetrate
+pub fn step(ctx: &mut Context, ships: &mut Vec<Ship>, motions: &[Motion], pickups: &mut Pickups) {
+    let mut pairs = Vec::new();
+    let mut hits = Vec::new();
+
+    for (body_1, body_2, point, normal) in ctx.contacts() {
+        let first = ships.iter().position(|ship| Rc::ptr_eq(&ship.handle, &body_1));
+        let second = ships.iter().position(|ship| Rc::ptr_eq(&ship.handle, &body_2));
+
+        let (first, second) = match (first, second) {
+            (Some(first), Some(second)) if first != second => (first, second),
+            _ => continue
+        };
+
+        let (a, b) = (first.min(second), first.max(second));
+
+        // Ships can touch in several places at once, but only the first counts
+        if pairs.contains(&(a, b)) {
+            continue;
+        }
+
+        pairs.push((a, b));
+
+        let impact = impact(&motions[first], &motions[second], point, normal);
+
+        if impact <= 0.0 {
+            continue;
+        }
+
+        let (part_a, part_b) = match (ships[a].nearest_component(point), ships[b].nearest_component(point)) {
+            (Some(part_a), Some(part_b)) => (part_a, part_b),
+            _ => continue
+        };
+
+        let (density_a, density_b) = (ships[a].components[part_a].tag.density(), ships[b].components[part_b].tag.density());
+        let total = density_a + density_b;
+
+        hits.push((a, point, impact * 2.0 * density_b / total));
+        hits.push((b, point, impact * 2.0 * density_a / total));
+    }
+
+    // Go from the last ship back, so removing a destroyed ship doesn't move the ones still to be hit
+    hits.sort_by(|&(a, _, _), &(b, _, _)| b.cmp(&a));
+
+    let mut debris = Vec::new();
+    let mut destroyed = None;
+
+    for (i, point, amount) in hits {
+        if destroyed == Some(i) {
+            continue;
+        }
+
+        let damage = Damage {
+            amount,
+            penetration: 0.0
+        };
+
+        if ships[i].damage(point, &damage, ctx, &mut debris, pickups) {
+            ships.remove(i);
+            destroyed = Some(i);
+        }
+    }
+
+    ships.extend(debris);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{Isometry2, Vector1};
+
+    fn motion(x: f32, lin_vel: f32, ang_vel: f32) -> Motion {
+        Motion {
+            position: Isometry2::new(Vector2::new(x, 0.0), 0.0),
+            centre: Point2::new(x, 0.0),
+            lin_vel: Vector2::new(lin_vel, 0.0),
+            ang_vel: Vector1::new(ang_vel)
+        }
+    }
+
+    // Two ships either side of the origin meeting there
+    fn meeting(first: &Motion, second: &Motion, point: Point2<f32>) -> f32 {
+        impact(first, second, point, Vector2::new(1.0, 0.0))
+    }
+
+    #[test]
+    fn head_on() {
+        let damage = meeting(&motion(-50.0, 80.0, 0.0), &motion(50.0, -80.0, 0.0), Point2::origin());
+        assert!((damage - (160.0 - MIN_IMPACT_SPEED) * DAMAGE_PER_SPEED).abs() < 1e-3);
+    }
+
+    #[test]
+    fn scraping_past() {
+        let mut first = motion(-50.0, 0.0, 0.0);
+        let mut second = motion(50.0, 0.0, 0.0);
+        first.lin_vel = Vector2::new(0.0, 200.0);
+        second.lin_vel = Vector2::new(0.0, -200.0);
+
+        assert_eq!(meeting(&first, &second, Point2::origin()), 0.0);
+    }
+
+    #[test]
+    fn bumping_or_parting() {
+        assert_eq!(meeting(&motion(-50.0, 30.0, 0.0), &motion(50.0, -30.0, 0.0), Point2::origin()), 0.0);
+        assert_eq!(meeting(&motion(-50.0, -80.0, 0.0), &motion(50.0, 80.0, 0.0), Point2::origin()), 0.0);
+    }
+
+    #[test]
+    fn spinning_into_something() {
+        // Spinning at 4 radians a second brings a point 50 along and 30 across from the centre in at 120
+        let damage = meeting(&motion(-50.0, 0.0, 4.0), &motion(50.0, 0.0, 0.0), Point2::new(0.0, -30.0));
+        assert!((damage - (120.0 - MIN_IMPACT_SPEED) * DAMAGE_PER_SPEED).abs() < 1e-3);
+    }
+}
@@ -1,6 +1,9 @@
 use sdl2::pixels::Color;
 
-pub const BLACK: Color = Color {r:   0, g:   0, b:   0, a: 255};
-pub const RED:   Color = Color {r: 255, g:   0, b:   0, a: 255};
-pub const GREEN: Color = Color {r:   0, g: 255, b:   0, a: 255};
-pub const WHITE: Color = Color {r: 255, g: 255, b: 255, a: 255};
\ No newline at end of file
+pub const BLACK:  Color = Color {r:   0, g:   0, b:   0, a: 255};
+pub const RED:    Color = Color {r: 255, g:   0, b:   0, a: 255};
+pub const GREEN:  Color = Color {r:   0, g: 255, b:   0, a: 255};
+pub const WHITE:  Color = Color {r: 255, g: 255, b: 255, a: 255};
+pub const YELLOW: Color = Color {r: 255, g: 230, b:  90, a: 255};
+pub const ORANGE: Color = Color {r: 255, g: 140, b:  40, a: 255};
+pub const BLUE:   Color = Color {r: 100, g: 160, b: 255, a: 255};
@@ -1,74 +1,156 @@
-use sdl2::render::{TextureCreator, WindowCanvas};
-use sdl2::rect::{Rect};
-use sdl2::render::BlendMode;
-use sdl2::video::WindowContext;
 use sdl2::pixels::Color;
 use nphysics2d::world::World;
-use nphysics2d::object::{RigidBody, RigidBodyHandle};
-use nalgebra::{Translation2, UnitComplex};
+use nphysics2d::object::{RigidBody, RigidBodyHandle, WorldObject};
+use nalgebra::{Translation2, UnitComplex, Point2, Vector2};
 
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::error::Error;
 
-use colours::BLACK;
-use resources::{Resources, Image};
+use renderer::{Renderer, NullRenderer};
+use resources::Image;
+use camera::Camera;
+
+// The segments of a seven segment digit as pairs of corners, where the corners are numbered
+// 0 top left, 1 top right, 2 middle left, 3 middle right, 4 bottom left and 5 bottom right
+const SEGMENTS: [(usize, usize); 7] = [(0, 1), (1, 3), (3, 5), (4, 5), (2, 4), (0, 2), (2, 3)];
+// The segments lit for each digit, with bit n set for SEGMENTS[n]
+const DIGITS: [u8; 10] = [0b011_1111, 0b000_0110, 0b101_1011, 0b100_1111, 0b110_0110, 0b110_1101, 0b111_1101, 0b000_0111, 0b111_1111, 0b110_1111];
+// Just the middle segment
+const MINUS: u8 = 0b100_0000;
 
 pub struct Context<'a> {
-    canvas: WindowCanvas,
     world: World<f32>,
-    resources: Resources<'a>
+    renderer: Box<Renderer + 'a>,
+    pub camera: Camera,
+    // The part of the window being drawn to, when the screen is split
+    viewport: Option<(i32, i32, u32, u32)>,
+    // Whether to draw debugging overlays
+    pub debug: bool
 }
 
 impl<'a> Context<'a> {
-    pub fn new(mut canvas: WindowCanvas, texture_creator: &'a TextureCreator<WindowContext>) -> Result<Self, Box<Error>> {
-        canvas.set_blend_mode(BlendMode::Blend);
-        canvas.set_draw_color(BLACK);
+    pub fn new(renderer: Box<Renderer + 'a>) -> Self {
+        let (width, height) = renderer.size();
 
-        Ok(Self {
-            resources: Resources::new(texture_creator)?,
+        Self {
             world: World::new(),
-            canvas
-        })
+            camera: Camera::new(width as f32, height as f32),
+            renderer,
+            viewport: None,
+            debug: false
+        }
+    }
+
+    // A context that simulates the world but doesn't draw anything
+    pub fn headless() -> Self {
+        Self::new(Box::new(NullRenderer))
     }
 
     pub fn set_colour(&mut self, colour: Color) {
-        self.canvas.set_draw_color(colour);
+        self.renderer.set_colour(colour);
     }
 
+    // Drawing functions take world coordinates, unless they end in _screen
+
     pub fn draw_point(&mut self, x: i32, y: i32) {
-        self.canvas.draw_point((x, y)).unwrap();
+        let (x, y) = self.camera.world_to_screen(x as f32, y as f32);
+        self.renderer.draw_point(x.round() as i32, y.round() as i32);
+    }
+
+    pub fn draw_point_screen(&mut self, x: i32, y: i32) {
+        self.renderer.draw_point(x, y);
     }
 
     pub fn draw_line(&mut self, x_1: f32, y_1: f32, x_2: f32, y_2: f32) {
-        self.canvas.draw_line((x_1 as i32, y_1 as i32), (x_2 as i32, y_2 as i32)).unwrap();
+        let (x_1, y_1) = self.camera.world_to_screen(x_1, y_1);
+        let (x_2, y_2) = self.camera.world_to_screen(x_2, y_2);
+        self.renderer.draw_line(x_1, y_1, x_2, y_2);
+    }
+
+    pub fn draw_line_screen(&mut self, x_1: f32, y_1: f32, x_2: f32, y_2: f32) {
+        self.renderer.draw_line(x_1, y_1, x_2, y_2);
     }
 
     pub fn clear(&mut self) {
-        self.canvas.clear();
+        self.renderer.clear();
     }
 
     pub fn present(&mut self) {
-        self.canvas.present();
+        self.renderer.present();
     }
 
-    fn to_rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
-        Rect::new((x - width / 2.0).round() as i32, (y - height / 2.0).round() as i32, width.round() as u32 + 1, height.round() as u32 + 1)
+    pub fn draw_rotated(&mut self, image: &Image, x: f32, y: f32, width: f32, height: f32, rotation: f32) {
+        let (x, y) = self.camera.world_to_screen(x, y);
+        let zoom = self.camera.zoom();
+        self.renderer.draw_rotated(image, x, y, width * zoom, height * zoom, rotation);
     }
 
-    pub fn draw_rotated(&mut self, image: &Image, x: f32, y: f32, width: f32, height: f32, rotation: f32) {
-        self.canvas.copy_ex(
-            self.resources.texture(image),
-            None, Some(Self::to_rect(x, y, width, height)),
-            f64::from(rotation), None,
-            false, false
-        ).unwrap();
+    pub fn draw_tinted(&mut self, image: &Image, x: f32, y: f32, width: f32, height: f32, rotation: f32, tint: Color) {
+        let (x, y) = self.camera.world_to_screen(x, y);
+        let zoom = self.camera.zoom();
+        self.renderer.draw_tinted(image, x, y, width * zoom, height * zoom, rotation, tint);
+    }
+
+    // Draw into part of the window, where screen coordinates start from its top left, or all of it with None
+    pub fn set_viewport(&mut self, viewport: Option<(i32, i32, u32, u32)>) {
+        self.viewport = viewport;
+        self.renderer.set_viewport(viewport);
+    }
+
+    pub fn window_size(&self) -> (u32, u32) {
+        self.renderer.size()
+    }
+
+    // The size of the area being drawn to
+    pub fn view_size(&self) -> (u32, u32) {
+        self.viewport.map_or_else(|| self.renderer.size(), |(_, _, width, height)| (width, height))
+    }
+
+    // Keep the camera the size of the view and move it towards a point in the world
+    pub fn update_camera(&mut self, target: (f32, f32), dt: f32) {
+        let (width, height) = self.view_size();
+        self.camera.resize(width as f32, height as f32);
+        self.camera.step(target, dt);
+    }
+
+    // Draw a number out of lines, centred on x, y in screen coordinates
+    pub fn draw_number(&mut self, number: i32, x: f32, y: f32, height: f32) {
+        let text = number.to_string();
+        let (width, spacing) = (height / 2.0, height * 0.75);
+        let left = x - (text.len() as f32 * spacing - (spacing - width)) / 2.0;
+        let top = y - height / 2.0;
+
+        for (i, character) in text.chars().enumerate() {
+            let segments = character.to_digit(10).map_or(MINUS, |digit| DIGITS[digit as usize]);
+            let x = left + i as f32 * spacing;
+            let corners = [(x, top), (x + width, top), (x, y), (x + width, y), (x, top + height), (x + width, top + height)];
+
+            for (n, &(start, end)) in SEGMENTS.iter().enumerate() {
+                if segments & (1 << n) != 0 {
+                    self.draw_line_screen(corners[start].0, corners[start].1, corners[end].0, corners[end].1);
+                }
+            }
+        }
     }
 
     pub fn step(&mut self, dt: f32) {
         self.world.step(dt);
     }
 
+    // Every pair of rigid bodies that were touching after the last step, along with where they touch and the normal there,
+    // which points from the first body towards the second
+    pub fn contacts(&self) -> Vec<(RigidBodyHandle<f32>, RigidBodyHandle<f32>, Point2<f32>, Vector2<f32>)> {
+        self.world.collision_world().contacts()
+            .filter_map(|(object_1, object_2, contact)| match (&object_1.data, &object_2.data) {
+                (&WorldObject::RigidBody(ref body_1), &WorldObject::RigidBody(ref body_2)) => {
+                    let point = Point2::from_coordinates((contact.world1.coords + contact.world2.coords) / 2.0);
+                    Some((body_1.clone(), body_2.clone(), point, contact.normal))
+                },
+                _ => None
+            })
+            .collect()
+    }
+
     pub fn add_rigid_body(&mut self, mut body: RigidBody<f32>, x: f32, y: f32, rotation: f32) -> Rc<RefCell<RigidBody<f32>>> {
         body.append_translation(&Translation2::new(x, y));
         body.append_rotation(&UnitComplex::new(rotation));
@@ -79,4 +161,4 @@ impl<'a> Context<'a> {
     pub fn remove_rigid_body(&mut self, body: &RigidBodyHandle<f32>) {
         self.world.remove_rigid_body(body);
     }
-}
\ No newline at end of file
+}
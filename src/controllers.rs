@@ -0,0 +1,65 @@
+use ships::Ship;
+use weapons::{Shots, GROUPS};
+use assist::Assist;
+
+// What a ship is being told to do for a tick, whether by a player or a pilot
+#[derive(Clone)]
+pub struct Input {
+    pub up: bool,
+    pub left: bool,
+    pub right: bool,
+    // How hard to thrust, from 0 to 1, which keys always push all the way but a stick can push part way
+    pub throttle: f32,
+    // Which flight assist mode is on
+    pub assist: Assist,
+    // Where each weapon group aims in world coordinates, and which groups fire
+    pub aims: [(f32, f32); GROUPS],
+    pub fire: [bool; GROUPS],
+    // A direction for every weapon to aim along from wherever it is on the ship, which a stick gives in place of the points
+    pub aim_direction: Option<(f32, f32)>
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            up: false,
+            left: false,
+            right: false,
+            throttle: 1.0,
+            assist: Assist::Off,
+            aims: [(0.0, 0.0); GROUPS],
+            fire: [false; GROUPS],
+            aim_direction: None
+        }
+    }
+}
+
+// Something that can fly a ship, given the ship's index and every ship in the world
+pub trait Controller {
+    fn input(&mut self, ship: usize, ships: &[Ship]) -> Input;
+}
+
+// Step every ship that is being flown, with each player's input going to their ship and pilots flying the rest
+pub fn step(ships: &mut Vec<Ship>, mut players: Vec<Option<Input>>, shots: &mut Shots) {
+    for i in 0 .. ships.len() {
+        let input = if let Some(player) = ships[i].player {
+            players.get_mut(player).and_then(|input| input.take())
+        } else {
+            // Take the pilot out of the ship while it looks around, so it can see every ship including its own
+            let pilot = ships[i].pilot.take();
+
+            match pilot {
+                Some(mut pilot) => {
+                    let input = pilot.input(i, ships);
+                    ships[i].pilot = Some(pilot);
+                    Some(input)
+                },
+                None => None
+            }
+        };
+
+        if let Some(input) = input {
+            ships[i].step(&input, shots);
+        }
+    }
+}
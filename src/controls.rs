@@ -0,0 +1,437 @@
+use sdl2::keyboard::Keycode;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+use nalgebra::{Vector2, Rotation2};
+use alga::linear::Transformation;
+
+use camera::Camera;
+use colours::WHITE;
+use context::Context;
+use controllers::{Controller, Input};
+use assist::Assist;
+use selections::Selection;
+use ships::Ship;
+use weapons::GROUPS;
+
+// How fast keyboard aim turns, in radians per tick
+const AIM_RATE: f32 = 0.05;
+// How far ahead of the ship a keyboard aimed shot is pointed, and how big the crosshair there is
+const AIM_DISTANCE: f32 = 400.0;
+const CROSSHAIR_SIZE: f32 = 8.0;
+// How far a stick has to be pushed before it does anything, and a trigger pulled before it fires, out of 1
+const DEADZONE: f32 = 0.25;
+const TRIGGER_THRESHOLD: f32 = 0.5;
+
+// Which keys do what for a player, and whether they use the mouse
+pub struct Bindings {
+    up: &'static [Keycode],
+    left: &'static [Keycode],
+    right: &'static [Keycode],
+    // Turning the aim, for players without the mouse
+    aim_left: &'static [Keycode],
+    aim_right: &'static [Keycode],
+    // Going on to the next flight assist mode
+    assist: &'static [Keycode],
+    // The key that fires each weapon group
+    groups: [Keycode; GROUPS],
+    // Whether the mouse aims and its buttons fire
+    mouse: bool
+}
+
+// Playing alone, with either set of movement keys and the mouse
+pub static SOLO: Bindings = Bindings {
+    up: &[Keycode::W, Keycode::Up],
+    left: &[Keycode::A, Keycode::Left],
+    right: &[Keycode::D, Keycode::Right],
+    aim_left: &[],
+    aim_right: &[],
+    assist: &[Keycode::F],
+    groups: [Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4],
+    mouse: true
+};
+
+// The first of two players sharing a keyboard, on the left hand side
+pub static PLAYER_ONE: Bindings = Bindings {
+    up: &[Keycode::W],
+    left: &[Keycode::A],
+    right: &[Keycode::D],
+    aim_left: &[Keycode::Q],
+    aim_right: &[Keycode::E],
+    assist: &[Keycode::F],
+    groups: [Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4],
+    mouse: false
+};
+
+// The second of two players sharing a keyboard, on the arrow keys and number pad
+pub static PLAYER_TWO: Bindings = Bindings {
+    up: &[Keycode::Up],
+    left: &[Keycode::Left],
+    right: &[Keycode::Right],
+    aim_left: &[Keycode::Kp4],
+    aim_right: &[Keycode::Kp6],
+    assist: &[Keycode::KpPeriod],
+    groups: [Keycode::Kp0, Keycode::KpEnter, Keycode::Kp1, Keycode::Kp2],
+    mouse: false
+};
+
+// A game controller's sticks, triggers and shoulder buttons, as SDL last reported them
+struct Pad {
+    // The instance id SDL tags the controller's events with
+    id: i32,
+    left_stick: (f32, f32),
+    right_stick: (f32, f32),
+    triggers: (f32, f32),
+    shoulders: (bool, bool),
+    // Which way the right stick last pointed, where the aim stays once it's let go
+    aim: Option<(f32, f32)>
+}
+
+impl Pad {
+    fn new(id: i32) -> Self {
+        Self {
+            id,
+            left_stick: (0.0, 0.0),
+            right_stick: (0.0, 0.0),
+            triggers: (0.0, 0.0),
+            shoulders: (false, false),
+            aim: None
+        }
+    }
+
+    // Pushing the left stick forwards thrusts forwards and pushing it to the side turns that way, both at once on the diagonals,
+    // harder the further it goes, and pulling it back does nothing
+    fn thrust(&self, input: &mut Input) {
+        let (x, y) = self.left_stick;
+        let length = (x * x + y * y).sqrt();
+
+        if length < DEADZONE {
+            return;
+        }
+
+        input.up = -y > DEADZONE;
+        input.left = x < -DEADZONE;
+        input.right = x > DEADZONE;
+        input.throttle = ((length - DEADZONE) / (1.0 - DEADZONE)).min(1.0);
+    }
+
+    // The right trigger fires the first group, the left trigger the second and the shoulder buttons the rest
+    fn fire(&self) -> [bool; GROUPS] {
+        [self.triggers.1 > TRIGGER_THRESHOLD, self.triggers.0 > TRIGGER_THRESHOLD, self.shoulders.1, self.shoulders.0]
+    }
+}
+
+pub struct Controls {
+    bindings: &'static Bindings,
+    up: bool,
+    left: bool,
+    right: bool,
+    aim_left: bool,
+    aim_right: bool,
+    // The aim relative to the ship's nose, for keyboard aiming, and the point that comes to in the world
+    aim: f32,
+    aim_point: (f32, f32),
+    // The mouse in world coordinates, and where it is on the screen
+    mouse: (f32, f32),
+    screen_mouse: (i32, i32),
+    mouse_down: bool,
+    right_mouse_down: bool,
+    // Weapon groups held down on their keys
+    groups: [bool; GROUPS],
+    // The flight assist mode, and whether its key is down so holding it only changes the mode once
+    assist: Assist,
+    assist_held: bool,
+    // A game controller, which takes over from the mouse or aim keys while it's plugged in
+    pad: Option<Pad>,
+    pub selection: Option<Selection>
+}
+
+impl Controls {
+    pub fn new(bindings: &'static Bindings) -> Self {
+        Self {
+            bindings,
+            up: false,
+            left: false,
+            right: false,
+            aim_left: false,
+            aim_right: false,
+            aim: 0.0,
+            aim_point: (0.0, 0.0),
+            mouse: (0.0, 0.0),
+            screen_mouse: (0, 0),
+            mouse_down: false,
+            right_mouse_down: false,
+            groups: [false; GROUPS],
+            assist: Assist::Off,
+            assist_held: false,
+            pad: None,
+            selection: None
+        }
+    }
+
+    pub fn uses_mouse(&self) -> bool {
+        self.bindings.mouse
+    }
+
+    pub fn handle_key(&mut self, key: Keycode, pressed: bool) {
+        let bindings = self.bindings;
+
+        if bindings.up.contains(&key) {
+            self.up = pressed;
+        }
+
+        if bindings.left.contains(&key) {
+            self.left = pressed;
+        }
+
+        if bindings.right.contains(&key) {
+            self.right = pressed;
+        }
+
+        if bindings.aim_left.contains(&key) {
+            self.aim_left = pressed;
+        }
+
+        if bindings.aim_right.contains(&key) {
+            self.aim_right = pressed;
+        }
+
+        if bindings.assist.contains(&key) {
+            if pressed && !self.assist_held {
+                self.next_assist();
+            }
+
+            self.assist_held = pressed;
+        }
+
+        for (group, &group_key) in bindings.groups.iter().enumerate() {
+            if key == group_key {
+                self.groups[group] = pressed;
+            }
+        }
+    }
+
+    pub fn has_pad(&self) -> bool {
+        self.pad.is_some()
+    }
+
+    // Start taking input from a game controller, by the instance id its events come with
+    pub fn attach_pad(&mut self, id: i32) {
+        self.pad = Some(Pad::new(id));
+    }
+
+    // Stop using a game controller if it's this player's, such as when it has been unplugged
+    pub fn detach_pad(&mut self, id: i32) {
+        if self.pad.as_ref().map_or(false, |pad| pad.id == id) {
+            self.pad = None;
+        }
+    }
+
+    pub fn handle_axis(&mut self, id: i32, axis: Axis, value: i16) {
+        let pad = match self.pad {
+            Some(ref mut pad) if pad.id == id => pad,
+            _ => return
+        };
+
+        let value = (f32::from(value) / 32767.0).max(-1.0);
+
+        match axis {
+            Axis::LeftX => pad.left_stick.0 = value,
+            Axis::LeftY => pad.left_stick.1 = value,
+            Axis::RightX => pad.right_stick.0 = value,
+            Axis::RightY => pad.right_stick.1 = value,
+            Axis::TriggerLeft => pad.triggers.0 = value,
+            Axis::TriggerRight => pad.triggers.1 = value
+        }
+
+        // Only change the aim while the stick is pushed, so it doesn't spring back to the middle
+        let (x, y) = pad.right_stick;
+        let length = (x * x + y * y).sqrt();
+
+        if length >= DEADZONE {
+            pad.aim = Some((x / length, y / length));
+        }
+    }
+
+    pub fn handle_button(&mut self, id: i32, button: Button, pressed: bool) {
+        if let Some(ref mut pad) = self.pad {
+            if pad.id == id {
+                match button {
+                    Button::LeftShoulder => pad.shoulders.0 = pressed,
+                    Button::RightShoulder => pad.shoulders.1 = pressed,
+                    _ => {}
+                }
+            }
+        }
+
+        if button == Button::Y && pressed && self.pad.as_ref().map_or(false, |pad| pad.id == id) {
+            self.next_assist();
+        }
+    }
+
+    // Go on to the next flight assist mode. Aiming with the keys is relative to the nose, so pointing the nose at the aim
+    // would only chase it round, and is left out for players doing that
+    fn next_assist(&mut self) {
+        self.assist = self.assist.next();
+
+        if self.assist == Assist::PointAtAim && !self.bindings.mouse && self.pad.is_none() {
+            self.assist = self.assist.next();
+        }
+    }
+
+    // The flight assist mode the player has picked, for showing on their HUD
+    pub fn assist(&self) -> Assist {
+        self.assist
+    }
+
+    pub fn handle_mouse_down(&mut self) {
+        self.mouse_down = true;
+        self.selection = Some(Selection::new(self.mouse));
+    }
+
+    pub fn handle_mouse_up(&mut self) {
+        self.mouse_down = false;
+        self.selection = None;
+    }
+
+    pub fn handle_right_mouse(&mut self, pressed: bool) {
+        self.right_mouse_down = pressed;
+    }
+
+    pub fn move_mouse(&mut self, x: i32, y: i32, camera: &Camera) {
+        self.screen_mouse = (x, y);
+        self.update_mouse(camera);
+    }
+
+    // Work out where the mouse is in the world, which changes as the camera moves even if the mouse doesn't
+    pub fn update_mouse(&mut self, camera: &Camera) {
+        self.mouse = camera.screen_to_world(self.screen_mouse.0 as f32, self.screen_mouse.1 as f32);
+
+        if self.mouse_down {
+            if let Some(ref mut sel) = self.selection {
+                sel.update_corner(self.mouse);
+            }
+        }
+    }
+
+    // Draw a crosshair where a player on the keyboard or a game controller is pointing
+    pub fn draw_aim(&self, ctx: &mut Context) {
+        if self.bindings.mouse && self.pad.is_none() {
+            return;
+        }
+
+        let (x, y) = self.aim_point;
+        ctx.set_colour(WHITE);
+        ctx.draw_line(x - CROSSHAIR_SIZE, y, x + CROSSHAIR_SIZE, y);
+        ctx.draw_line(x, y - CROSSHAIR_SIZE, x, y + CROSSHAIR_SIZE);
+    }
+}
+
+// The game controllers that are plugged in, which are handed out to players as they're connected
+pub struct Pads {
+    subsystem: GameControllerSubsystem,
+    open: Vec<GameController>
+}
+
+impl Pads {
+    // Controllers that are already plugged in show up as being connected once events start coming in
+    pub fn new(subsystem: GameControllerSubsystem) -> Self {
+        Self {
+            subsystem,
+            open: Vec::new()
+        }
+    }
+
+    // Deal with a controller being plugged in, pulled out or used, and return whether the event was one of those
+    pub fn handle_event(&mut self, event: &Event, controls: &mut [Controls]) -> bool {
+        match *event {
+            Event::ControllerDeviceAdded {which, ..} => match self.subsystem.open(which as u32) {
+                Ok(pad) => {
+                    // The first player has the mouse, so give controllers out starting from the last player
+                    if let Some(controls) = controls.iter_mut().rev().find(|controls| !controls.has_pad()) {
+                        controls.attach_pad(pad.instance_id());
+                    }
+
+                    println!("Connected {}", pad.name());
+                    self.open.push(pad);
+                },
+                Err(err) => println!("Couldn't open controller: {}", err)
+            },
+            Event::ControllerDeviceRemoved {which, ..} => {
+                self.open.retain(|pad| pad.instance_id() != which);
+                controls.iter_mut().for_each(|controls| controls.detach_pad(which));
+            },
+            Event::ControllerAxisMotion {which, axis, value, ..} => controls.iter_mut().for_each(|controls| controls.handle_axis(which, axis, value)),
+            Event::ControllerButtonDown {which, button, ..} => controls.iter_mut().for_each(|controls| controls.handle_button(which, button, true)),
+            Event::ControllerButtonUp   {which, button, ..} => controls.iter_mut().for_each(|controls| controls.handle_button(which, button, false)),
+            _ => return false
+        }
+
+        true
+    }
+}
+
+impl Controller for Controls {
+    // Aim everything at the mouse, with the left mouse button firing the first group and the right firing the second,
+    // or for players on the keyboard, at a point ahead of the ship that the aim keys turn.
+    // A game controller flies with the left stick, aims the way the right stick points and fires with the triggers and shoulders
+    fn input(&mut self, ship: usize, ships: &[Ship]) -> Input {
+        let mut input = Input {
+            up: self.up,
+            left: self.left,
+            right: self.right,
+            fire: self.groups,
+            assist: self.assist,
+            ..Input::default()
+        };
+
+        if self.bindings.mouse {
+            input.fire[0] |= self.mouse_down;
+            input.fire[1] |= self.right_mouse_down;
+        }
+
+        if let Some(ref pad) = self.pad {
+            for (fire, pad_fire) in input.fire.iter_mut().zip(pad.fire().iter()) {
+                *fire |= *pad_fire;
+            }
+
+            // The stick only flies the ship while none of the movement keys are held
+            if !(self.up || self.left || self.right) {
+                pad.thrust(&mut input);
+            }
+        }
+
+        let (centre, rotation) = {
+            let rigid_body = ships[ship].handle.borrow();
+            let position = rigid_body.position();
+            (position.translation.vector, position.rotation.arg())
+        };
+
+        let aim = match self.pad.as_ref().and_then(|pad| pad.aim) {
+            // Weapons aim along the stick's direction, and the crosshair just shows which way that is
+            Some((x, y)) => {
+                input.aim_direction = Some((x, y));
+                let point = centre + Vector2::new(x, y) * AIM_DISTANCE;
+                self.aim_point = (point.x, point.y);
+                self.aim_point
+            },
+            None if self.bindings.mouse => self.mouse,
+            None => {
+                if self.aim_left {
+                    self.aim -= AIM_RATE;
+                }
+
+                if self.aim_right {
+                    self.aim += AIM_RATE;
+                }
+
+                let point = centre + Rotation2::new(rotation + self.aim).transform_vector(&Vector2::new(0.0, -AIM_DISTANCE));
+                self.aim_point = (point.x, point.y);
+                self.aim_point
+            }
+        };
+
+        input.aims = [aim; GROUPS];
+        input
+    }
+}
@@ -0,0 +1,170 @@
+use sdl2::keyboard::Keycode;
+use nalgebra::Vector2;
+
+use std::path::{Path, PathBuf};
+
+use blueprints::Blueprint;
+use colours::GREEN;
+use context::Context;
+use selections::Selection;
+use ships::{Ship, Component, ComponentType, Rotation, SIZE};
+use weapons::WeaponType;
+
+// The component type each number key picks
+fn palette(key: Keycode) -> Option<ComponentType> {
+    Some(match key {
+        Keycode::Num1 => ComponentType::Metal,
+        Keycode::Num2 => ComponentType::Pipe,
+        Keycode::Num3 => ComponentType::Reactor,
+        Keycode::Num4 => ComponentType::Engine,
+        Keycode::Num5 => ComponentType::Rock,
+        Keycode::Num6 => ComponentType::hardpoint(WeaponType::Laser),
+        Keycode::Num7 => ComponentType::hardpoint(WeaponType::TractionBeam),
+        Keycode::Num8 => ComponentType::hardpoint(WeaponType::Cannon),
+        Keycode::Num9 => ComponentType::hardpoint(WeaponType::Missile),
+        Keycode::Num0 => ComponentType::hardpoint(WeaponType::MassDriver),
+        Keycode::Minus => ComponentType::magazine(),
+        Keycode::Equals => ComponentType::collector(),
+        _ => return None
+    })
+}
+
+pub struct Editor {
+    pub active: bool,
+    // The grid positions of the selected components
+    selected: Vec<(i8, i8)>,
+    // The component type placed by clicking on an empty square
+    brush: ComponentType,
+    // Where the blueprint is saved to
+    path: PathBuf
+}
+
+impl Editor {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            active: false,
+            selected: Vec::new(),
+            brush: ComponentType::Metal,
+            path: path.to_path_buf()
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        self.selected.clear();
+    }
+
+    fn is_selected(&self, component: &Component) -> bool {
+        self.selected.contains(&(component.x, component.y))
+    }
+
+    pub fn handle_key(&mut self, key: Keycode, ship: &mut Ship, ctx: &mut Context) {
+        match key {
+            Keycode::Delete | Keycode::Backspace => self.delete(ship, ctx),
+            Keycode::R => self.rotate(ship, ctx),
+            Keycode::F5 => self.save(ship),
+            _ => if let Some(tag) = palette(key) {
+                self.replace(tag, ship, ctx);
+            }
+        }
+    }
+
+    // Select the components under a finished selection box, or place a component if it was just a click on an empty square
+    pub fn select(&mut self, selection: &Selection, ship: &mut Ship, ctx: &mut Context) {
+        if !selection.is_click() {
+            self.selected = selection.components(ship).iter().map(|component| (component.x, component.y)).collect();
+            return;
+        }
+
+        let (x, y) = ship.grid_position(selection.start());
+        self.selected = vec![(x, y)];
+
+        let occupied = ship.components.iter().any(|component| component.x == x && component.y == y);
+
+        // Only place components that would be attached to the ship
+        if !occupied && ship.components.iter().any(|component| component.adjacent(x, y)) {
+            ship.components.push(Component::new(self.brush.clone(), x, y, Rotation::Up));
+            ship.rebuild(ctx);
+        }
+    }
+
+    fn delete(&mut self, ship: &mut Ship, ctx: &mut Context) {
+        // A ship needs at least one component
+        if self.selected.is_empty() || ship.components.iter().all(|component| self.is_selected(component)) {
+            return;
+        }
+
+        ship.components.retain(|component| !self.is_selected(component));
+        self.selected.clear();
+        ship.rebuild(ctx);
+    }
+
+    fn rotate(&mut self, ship: &mut Ship, ctx: &mut Context) {
+        if self.selected.is_empty() {
+            return;
+        }
+
+        for component in ship.components.iter_mut().filter(|component| self.is_selected(component)) {
+            component.rotation = component.rotation.clockwise();
+        }
+
+        // Rotating pipes changes the power network
+        ship.rebuild(ctx);
+    }
+
+    fn replace(&mut self, tag: ComponentType, ship: &mut Ship, ctx: &mut Context) {
+        self.brush = tag;
+
+        if self.selected.is_empty() {
+            return;
+        }
+
+        for component in ship.components.iter_mut().filter(|component| self.is_selected(component)) {
+            *component = Component::new(self.brush.clone(), component.x, component.y, component.rotation.clone());
+        }
+
+        ship.rebuild(ctx);
+    }
+
+    fn save(&self, ship: &Ship) {
+        // Keep the spawn point and comments of the existing blueprint, if there is one
+        let blueprint = match Blueprint::load(&self.path) {
+            Ok(existing) => Blueprint {
+                components: ship.components.clone(),
+                ..existing
+            },
+            Err(_) => {
+                let rigid_body = ship.handle.borrow();
+                let position = rigid_body.position();
+                let spawn = (position.translation.vector.x, position.translation.vector.y, position.rotation.arg());
+                Blueprint::new(ship.components.clone(), spawn)
+            }
+        };
+
+        match blueprint.save(&self.path) {
+            Ok(()) => println!("Saved ship to {}", self.path.display()),
+            Err(err) => eprintln!("Failed to save ship to {}: {}", self.path.display(), err)
+        }
+    }
+
+    // Outline the selected components
+    pub fn draw(&self, ctx: &mut Context, ship: &Ship) {
+        let rigid_body = ship.handle.borrow();
+        let position = rigid_body.position();
+
+        ctx.set_colour(GREEN);
+
+        for component in ship.components.iter().filter(|component| self.is_selected(component)) {
+            let (pos, _) = component.position(position);
+
+            let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter()
+                .map(|&(x, y)| pos + position.rotation * Vector2::new(x, y) * (SIZE / 2.0))
+                .collect::<Vec<_>>();
+
+            for i in 0 .. corners.len() {
+                let (start, end) = (corners[i], corners[(i + 1) % corners.len()]);
+                ctx.draw_line(start.x, start.y, end.x, end.y);
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use context::Context;
+use ships::Ship;
+use weapons::Shots;
+use pickups::Pickups;
+use asteroids::AsteroidField;
+use controllers::{self, Input};
+use collisions;
+
+// How close to the players asteroids can appear when the game starts, and once it's running so they appear off screen
+const START_CLEARANCE: f32 = 300.0;
+pub const SPAWN_CLEARANCE: f32 = 1000.0;
+
+// Everything in the world that changes from tick to tick
+pub struct Game {
+    pub ships: Vec<Ship>,
+    pub shots: Shots,
+    pub pickups: Pickups,
+    pub asteroids: AsteroidField,
+    // How many ticks have been run
+    pub ticks: u32,
+    // Where the players are, which is where the asteroids are kept stocked
+    centre: (f32, f32),
+    // The id the next new ship gets
+    next_id: u32
+}
+
+// The middle of all the players' ships, if there are any left
+fn players_centre(ships: &[Ship]) -> Option<(f32, f32)> {
+    let positions = ships.iter()
+        .filter(|ship| ship.player.is_some())
+        .map(|ship| ship.position())
+        .collect::<Vec<_>>();
+
+    if positions.is_empty() {
+        None
+    } else {
+        let count = positions.len() as f32;
+        let (x, y) = positions.iter().fold((0.0, 0.0), |(x, y), &(ship_x, ship_y)| (x + ship_x, y + ship_y));
+        Some((x / count, y / count))
+    }
+}
+
+impl Game {
+    // Start with some ships and asteroids scattered around the players.
+    // Everything random in the game comes from the seed, so the same seed and inputs play out the same way
+    pub fn new(ctx: &mut Context, mut ships: Vec<Ship>, seed: u32) -> Self {
+        let mut asteroids = AsteroidField::new(seed);
+        let centre = players_centre(&ships).unwrap_or((0.0, 0.0));
+        asteroids.populate(ctx, &mut ships, centre, START_CLEARANCE);
+
+        Self::restore(ships, asteroids, Pickups::new(seed), 0)
+    }
+
+    // Put a game back together from its parts, like when loading a save
+    pub fn restore(ships: Vec<Ship>, asteroids: AsteroidField, pickups: Pickups, ticks: u32) -> Self {
+        let mut game = Self {
+            centre: players_centre(&ships).unwrap_or((0.0, 0.0)),
+            ships,
+            shots: Shots::default(),
+            pickups,
+            asteroids,
+            ticks,
+            next_id: 1
+        };
+
+        game.number_ships();
+        game
+    }
+
+    // Give every ship that has appeared since the last time an id of its own
+    fn number_ships(&mut self) {
+        for ship in self.ships.iter_mut().filter(|ship| ship.id == 0) {
+            ship.id = self.next_id;
+            self.next_id += 1;
+        }
+    }
+
+    // Bring a ship into the game partway through, like when a player joins
+    pub fn add_ship(&mut self, ship: Ship) {
+        self.ships.push(ship);
+        self.number_ships();
+    }
+
+    // The index of the ship a player is flying, if it's still in one piece
+    pub fn player_ship(&self, player: usize) -> Option<usize> {
+        self.ships.iter().position(|ship| ship.player == Some(player))
+    }
+
+    // Advance the world by one tick, with each player's input flying their ship
+    pub fn tick(&mut self, ctx: &mut Context, players: Vec<Option<Input>>, dt: f32) {
+        // Rays only act for a tick, but are kept until the next one so they can be drawn
+        self.shots.rays.clear();
+        self.ships.iter_mut().for_each(|ship| ship.start_tick());
+
+        // Collisions do damage going by how things were moving before they hit
+        let motions = self.ships.iter().map(|ship| ship.motion()).collect::<Vec<_>>();
+        ctx.step(dt);
+        collisions::step(ctx, &mut self.ships, &motions, &mut self.pickups);
+
+        controllers::step(&mut self.ships, players, &mut self.shots);
+        self.shots.step(dt, &mut self.ships, &mut self.pickups, ctx);
+        self.pickups.step(dt, &mut self.ships, ctx);
+
+        // Replace rocks that have been destroyed or left behind, staying where the players were if they're all gone
+        self.centre = players_centre(&self.ships).unwrap_or(self.centre);
+        self.asteroids.populate(ctx, &mut self.ships, self.centre, SPAWN_CLEARANCE);
+        self.number_ships();
+
+        self.ticks += 1;
+    }
+
+    // Take everything out of the physics world, before replacing the game with another one
+    pub fn clear(&mut self, ctx: &mut Context) {
+        for ship in self.ships.drain(..) {
+            ctx.remove_rigid_body(&ship.handle);
+        }
+
+        for pickup in self.pickups.pickups.drain(..) {
+            ctx.remove_rigid_body(&pickup.handle);
+        }
+
+        self.shots = Shots::default();
+    }
+
+    // Draw everything part way from where it was before the last tick to where it is now
+    pub fn draw(&self, ctx: &mut Context, alpha: f32) {
+        self.ships.iter().for_each(|ship| ship.draw(ctx, alpha));
+        self.shots.draw(ctx, alpha);
+        self.pickups.draw(ctx);
+    }
+}
@@ -0,0 +1,52 @@
+use colours::{RED, WHITE, YELLOW};
+use context::Context;
+use ships::{Ship, ComponentType};
+use assist::Assist;
+
+const MARGIN: f32 = 20.0;
+const TOP: f32 = 20.0;
+const BAR_WIDTH: f32 = 100.0;
+const BAR_HEIGHT: f32 = 6.0;
+const SPACING: f32 = 14.0;
+
+// Draw a bar filled up to a fraction of its width
+fn bar(ctx: &mut Context, x: f32, y: f32, fraction: f32) {
+    ctx.draw_line_screen(x, y, x + BAR_WIDTH, y);
+    ctx.draw_line_screen(x, y + BAR_HEIGHT, x + BAR_WIDTH, y + BAR_HEIGHT);
+    ctx.draw_line_screen(x, y, x, y + BAR_HEIGHT);
+    ctx.draw_line_screen(x + BAR_WIDTH, y, x + BAR_WIDTH, y + BAR_HEIGHT);
+
+    for i in 1 .. BAR_HEIGHT as i32 {
+        let line_y = y + i as f32;
+        ctx.draw_line_screen(x, line_y, x + BAR_WIDTH * fraction, line_y);
+    }
+}
+
+// Draw the heat of each of the ship's weapons, how much ammo it has left, how much ore it's carrying and
+// how much flight assist is doing, from a distance along the screen
+pub fn draw(ctx: &mut Context, ship: &Ship, assist: Assist, left: f32) {
+    let left = left + MARGIN;
+    let weapons = ship.components.iter().filter_map(|component| match component.tag {
+        ComponentType::Hardpoint(ref weapon) => Some(weapon),
+        _ => None
+    });
+
+    let mut y = TOP;
+
+    for weapon in weapons {
+        // The weapon's colour, or red while it's locked out from overheating
+        ctx.set_colour(if weapon.overheated() { RED } else { weapon.tag().colour() });
+        bar(ctx, left, y, weapon.heat());
+        y += SPACING;
+    }
+
+    ctx.set_colour(WHITE);
+    ctx.draw_number(ship.ammo() as i32, left + BAR_WIDTH / 2.0, y + SPACING / 2.0, 10.0);
+
+    ctx.set_colour(YELLOW);
+    ctx.draw_number(ship.ore() as i32, left + BAR_WIDTH / 2.0, y + SPACING * 2.0, 10.0);
+
+    // Each mode does what the ones before it do, so the bar fills up a step for each
+    ctx.set_colour(WHITE);
+    bar(ctx, left, y + SPACING * 3.0, assist.level() as f32 / Assist::PointAtAim.level() as f32);
+}
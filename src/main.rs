@@ -12,68 +12,420 @@ extern crate ord_subset;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::mouse::MouseButton;
-use rand::Rng;
+use sdl2::EventPump;
+
+use std::collections::HashMap;
+use std::env;
+use std::f32;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
 mod colours;
 mod ships;
 mod context;
+mod camera;
+mod renderer;
 mod resources;
 mod weapons;
+mod projectiles;
 mod selections;
+mod blueprints;
+mod power;
+mod editor;
+mod hud;
+mod starfield;
+mod asteroids;
+mod pickups;
+mod controllers;
+mod pilots;
+mod game;
+mod clock;
+mod replays;
+mod saves;
+mod controls;
+mod network;
+mod mirror;
+mod collisions;
+mod thrust;
+mod assist;
+mod random;
 
 const WIDTH: u32 = 1280;
 const HEIGHT: u32 = 800;
+const DT: f32 = 1.0 / 60.0;
+const SHIPS_DIR: &str = "ships";
+const PLAYER_SHIP: &str = "player";
+const SAVE_FILE: &str = "quicksave.save";
+// How far apart the players' ships start
+const PLAYER_SPACING: f32 = 300.0;
+// How much room to leave around the players when they share the screen
+const FIT_MARGIN: f32 = 600.0;
+// How often a server sends out the world, in ticks, and how long a player waits for a new ship after theirs is destroyed
+const SNAPSHOT_INTERVAL: u32 = 2;
+const RESPAWN_TICKS: u32 = 180;
+// How many ticks a client waits for the server to answer before asking to join again
+const JOIN_INTERVAL: u32 = 30;
+// How many pixels the view shakes by for each point of damage a player takes
+const SHAKE_PER_DAMAGE: f32 = 0.5;
 
-use ships::{Component, ComponentType, Ship, Rotation};
-use weapons::WeaponType;
+use ships::Ship;
 use context::Context;
+use camera::Camera;
+use renderer::SdlRenderer;
 use colours::{BLACK, WHITE};
-use selections::Selection;
-
-#[derive(Default)]
-pub struct Controls {
-    up: bool,
-    left: bool,
-    right: bool,
-    mouse: (f32, f32),
-    mouse_down: bool,
-    selection: Option<Selection>
+use blueprints::Blueprint;
+use editor::Editor;
+use starfield::Starfield;
+use controllers::{Controller, Input};
+use pilots::Pilot;
+use game::Game;
+use clock::Clock;
+use replays::{Replay, Playback};
+use saves::Save;
+use controls::{Controls, Pads, SOLO, PLAYER_ONE, PLAYER_TWO};
+use network::{Server, Connection, ClientEvent};
+use mirror::Mirror;
+
+// A player's ship from the player blueprint, with the players lined up side by side
+fn spawn_player(ctx: &mut Context, blueprint: &Blueprint, player: usize) -> Ship {
+    let (x, y, rotation) = blueprint.spawn;
+    let mut ship = Ship::new(ctx, blueprint.components.clone(), x + player as f32 * PLAYER_SPACING, y, rotation);
+    ship.player = Some(player);
+    ship
 }
 
-impl Controls {
-    fn handle_key(&mut self, key: Keycode, pressed: bool) {
-        match key {
-            Keycode::W | Keycode::Up    => self.up    = pressed,
-            Keycode::A | Keycode::Left  => self.left  = pressed,
-            Keycode::D | Keycode::Right => self.right = pressed,
-            _ => {}
+// Spawn every ship in the ships directory, with a copy of the player's ship for each player first and pilots flying the rest
+fn create_ships(ctx: &mut Context, players: usize) -> Vec<Ship> {
+    let mut blueprints = Blueprint::load_dir(Path::new(SHIPS_DIR)).unwrap();
+    blueprints.sort_by_key(|&(ref name, _)| name != PLAYER_SHIP);
+
+    let mut ships = Vec::new();
+
+    for (name, blueprint) in blueprints {
+        if name == PLAYER_SHIP {
+            ships.extend((0 .. players).map(|player| spawn_player(ctx, &blueprint, player)));
+        } else {
+            let (x, y, rotation) = blueprint.spawn;
+            let mut ship = Ship::new(ctx, blueprint.components, x, y, rotation);
+            ship.pilot = Some(Box::new(Pilot::new()));
+            ships.push(ship);
+        }
+    }
+
+    ships
+}
+
+// One player gets the whole keyboard and the mouse, and two share the keyboard between them
+fn create_controls(players: usize) -> Vec<Controls> {
+    match players {
+        1 => vec![Controls::new(&SOLO)],
+        2 => vec![Controls::new(&PLAYER_ONE), Controls::new(&PLAYER_TWO)],
+        _ => panic!("There can only be one or two players")
+    }
+}
+
+// What each player wants to do this tick, with nothing for players whose ships are gone or the first player's ship while it's being edited
+fn player_inputs(game: &Game, controls: &mut [Controls], editing: bool) -> Vec<Option<Input>> {
+    controls.iter_mut()
+        .enumerate()
+        .map(|(player, controls)| match game.player_ship(player) {
+            Some(_) if editing && player == 0 => None,
+            Some(ship) => Some(controls.input(ship, &game.ships)),
+            None => None
+        })
+        .collect()
+}
+
+// How much damage each player's ship has taken so far, so the damage they take over a frame can be found.
+// Pieces breaking off don't count, since nothing hit them
+fn player_damage(game: &Game, players: usize) -> Vec<Option<f32>> {
+    (0 .. players).map(|player| game.player_ship(player).map(|ship| game.ships[ship].damage_taken())).collect()
+}
+
+// Run a tick, with input played back from a replay until it runs out and the players' after that, recording whatever was used
+fn tick(ctx: &mut Context, game: &mut Game, players: Vec<Option<Input>>, recording: &mut Option<Replay>, playback: &mut Option<Playback>) {
+    let inputs = match *playback {
+        Some(ref playback) if !playback.finished(game.ticks) => playback.inputs(game.ticks),
+        _ => players
+    };
+
+    if let Some(ref mut recording) = *recording {
+        recording.record(&inputs);
+    }
+
+    game.tick(ctx, inputs, DT);
+
+    if let Some(ref mut recording) = *recording {
+        recording.record_checksum(game.ticks, &game.ships);
+    }
+
+    if let Some(ref mut playback) = *playback {
+        if playback.check(game.ticks, &game.ships) {
+            println!("Replay diverged at tick {}", game.ticks);
         }
     }
+}
+
+// End a recording early when something happens that a replay can't play back, keeping what was recorded up to then
+fn stop_recording(recording: &mut Option<Replay>, record: Option<&PathBuf>, reason: &str) {
+    if let (Some(replay), Some(path)) = (recording.take(), record) {
+        replay.save(path).unwrap();
+        println!("Stopped recording after {} ticks because {}", replay.len(), reason);
+    }
+}
+
+// The value given after a command line flag
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter().position(|arg| arg == flag).map(|i| args.get(i + 1).map(|value| value.as_str()).unwrap_or_else(|| panic!("{} needs a value", flag)))
+}
+
+// Run the simulation for a number of ticks, or the length of the replay being played, without a window and print where everything ended up
+fn run_headless(ticks: Option<u32>, players: usize, mut playback: Option<Playback>, record: Option<PathBuf>, load: Option<Save>) {
+    let seed = playback.as_ref().map_or_else(rand::random, |playback| playback.seed());
+    let ticks = ticks.or_else(|| playback.as_ref().map(|playback| playback.len())).unwrap_or(600);
+
+    let mut ctx = Context::headless();
+    let ships = create_ships(&mut ctx, players);
+    let mut game = Game::new(&mut ctx, ships, seed);
+    let mut controls = create_controls(players);
+    let mut recording = Some(Replay::new(seed, players));
+
+    if let Some(save) = load {
+        game.clear(&mut ctx);
+        game = save.restore(&mut ctx).0;
+        // Replays start from a seed rather than a save, so there's nothing to play back or record
+        playback = None;
+        recording = None;
+    }
 
-    fn handle_mouse_down(&mut self) {
-        self.mouse_down = true;
-        self.selection = Some(Selection::new(self.mouse));
+    for _ in 0 .. ticks {
+        let inputs = player_inputs(&game, &mut controls, false);
+        tick(&mut ctx, &mut game, inputs, &mut recording, &mut playback);
     }
 
-    fn handle_mouse_up(&mut self) {
-        self.mouse_down = false;
-        self.selection = None;
+    if let (Some(path), Some(recording)) = (record, recording) {
+        recording.save(&path).unwrap();
     }
 
-    fn move_mouse(&mut self, x: i32, y: i32) {
-        self.mouse = (x as f32, y as f32);
+    println!("After {} ticks, {} ships:", ticks, game.ships.len());
+
+    for (i, ship) in game.ships.iter().enumerate() {
+        let rigid_body = ship.handle.borrow();
+        let position = rigid_body.position();
+        let (lin_vel, ang_vel) = (rigid_body.lin_vel(), rigid_body.ang_vel());
+
+        println!(
+            "  {}: {} components at ({:.2}, {:.2}) rotation {:.3}, velocity ({:.2}, {:.2}) spin {:.3}",
+            i, ship.components.len(),
+            position.translation.vector.x, position.translation.vector.y, position.rotation.arg(),
+            lin_vel.x, lin_vel.y, ang_vel.x
+        );
+    }
+}
+
+// Run a game for clients to join over the network, without a window
+fn run_server(address: &str) {
+    let seed = rand::random();
+    let mut ctx = Context::headless();
+    let ships = create_ships(&mut ctx, 0);
+    let mut game = Game::new(&mut ctx, ships, seed);
+    let blueprint = Blueprint::load(&Path::new(SHIPS_DIR).join(format!("{}.ship", PLAYER_SHIP))).unwrap();
+
+    let mut server = Server::bind(address, seed).unwrap();
+    println!("Serving on {}", address);
+
+    // How many ticks are left before each player whose ship was destroyed gets a new one
+    let mut respawns = HashMap::new();
+    let tick_length = Duration::new(0, (DT * 1_000_000_000.0) as u32);
+    let mut next_tick = Instant::now();
+
+    loop {
+        for event in server.receive() {
+            match event {
+                ClientEvent::Joined(player) => game.add_ship(spawn_player(&mut ctx, &blueprint, player)),
+                // The ship of a player who has gone is left to drift as a wreck
+                ClientEvent::Left(player) => {
+                    respawns.remove(&player);
+
+                    if let Some(ship) = game.player_ship(player) {
+                        game.ships[ship].player = None;
+                    }
+                }
+            }
+        }
+
+        for player in server.players() {
+            if game.player_ship(player).is_some() {
+                continue;
+            }
+
+            let wait = {
+                let wait = respawns.entry(player).or_insert(RESPAWN_TICKS);
+                *wait -= 1;
+                *wait
+            };
+
+            if wait == 0 {
+                respawns.remove(&player);
+                game.add_ship(spawn_player(&mut ctx, &blueprint, player));
+            }
+        }
+
+        let inputs = server.inputs();
+        game.tick(&mut ctx, inputs, DT);
+
+        if game.ticks % SNAPSHOT_INTERVAL == 0 {
+            server.send_snapshot(&game);
+        }
+
+        // Keep to real time, and start again from now rather than rushing to catch up if a tick ran long
+        next_tick += tick_length;
+        let now = Instant::now();
+
+        if next_tick > now {
+            thread::sleep(next_tick - now);
+        } else {
+            next_tick = now;
+        }
+    }
+}
+
+// Play on a server, showing the world as it sends it with the player's own ship flown ahead on their input
+fn run_client(ctx: &mut Context, event_pump: &mut EventPump, pads: &mut Pads, address: &str) {
+    let mut connection = Connection::connect(address).unwrap();
+    let mut controls = vec![Controls::new(&SOLO)];
+    let mut clock = Clock::new(DT);
+
+    // There's nothing to show until the server has answered
+    let mut mirror: Option<Mirror> = None;
+    let mut starfield: Option<Starfield> = None;
+    let mut waiting = 0;
+
+    'main: loop {
+        for event in event_pump.poll_iter() {
+            if pads.handle_event(&event, &mut controls) {
+                continue;
+            }
+
+            match event {
+                Event::Quit {..} => break 'main,
+                Event::KeyDown {keycode: Some(Keycode::F3), ..} => ctx.debug = !ctx.debug,
+                Event::KeyDown {keycode: Some(key), ..} => controls[0].handle_key(key, true),
+                Event::KeyUp {keycode: Some(key), ..} => controls[0].handle_key(key, false),
+                Event::MouseMotion {x, y, ..} => controls[0].move_mouse(x, y, &ctx.camera),
+                Event::MouseWheel {y, ..} => ctx.camera.zoom_by(y),
+                Event::MouseButtonDown {mouse_btn: MouseButton::Left, ..} => controls[0].handle_mouse_down(),
+                Event::MouseButtonDown {mouse_btn: MouseButton::Right, ..} => controls[0].handle_right_mouse(true),
+                Event::MouseButtonUp   {mouse_btn: MouseButton::Right, ..} => controls[0].handle_right_mouse(false),
+                Event::MouseButtonUp   {mouse_btn: MouseButton::Left, ..} => controls[0].handle_mouse_up(),
+                _ => {}
+            }
+        }
+
+        let snapshot = connection.receive();
+
+        if mirror.is_none() {
+            if let (Some(player), Some(seed)) = (connection.player, connection.starfield) {
+                println!("Joined {} as player {}", address, player);
+                mirror = Some(Mirror::new(player));
+                starfield = Some(Starfield::new(seed));
+            }
+        }
+
+        if let (Some(mirror), Some(snapshot)) = (mirror.as_mut(), snapshot) {
+            mirror.apply(ctx, snapshot, connection.pending(), DT);
+        }
+
+        for _ in 0 .. clock.ticks() {
+            match mirror {
+                // Input keeps being sent while the player's ship is gone, so the server knows they're still there
+                Some(ref mut mirror) => {
+                    let input = mirror.own_ship().map(|ship| controls[0].input(ship, &mirror.ships)).unwrap_or_default();
+                    connection.send_input(input.clone());
+                    mirror.tick(ctx, Some(input), DT);
+                },
+                None => {
+                    if waiting % JOIN_INTERVAL == 0 {
+                        connection.join();
+                    }
+
+                    waiting += 1;
+                }
+            }
+        }
+
+        let alpha = clock.alpha();
+
+        if let Some(ref mirror) = mirror {
+            if let Some(ship) = mirror.own_ship() {
+                let target = mirror.ships[ship].interpolate(alpha).translation.vector;
+                ctx.update_camera((target.x, target.y), clock.frame_time());
+            }
+        }
+
+        controls[0].update_mouse(&ctx.camera);
+
+        ctx.set_colour(BLACK);
+        ctx.clear();
 
-        if self.mouse_down {
-            if let Some(ref mut sel) = self.selection {
-                sel.update_corner(self.mouse);
+        if let Some(ref starfield) = starfield {
+            starfield.draw(ctx);
+        }
+
+        if let Some(ref mirror) = mirror {
+            mirror.draw(ctx, alpha);
+            controls[0].draw_aim(ctx);
+
+            if let Some(ship) = mirror.own_ship() {
+                hud::draw(ctx, &mirror.ships[ship], controls[0].assist(), 0.0);
             }
         }
+
+        ctx.present();
     }
+
+    connection.leave();
 }
 
 fn main() {
+    let args = env::args().collect::<Vec<_>>();
+
+    // Replays record the seed and the players' input each tick, so a game can be played back exactly
+    let mut playback = flag_value(&args, "--replay").map(|path| Playback::new(Replay::load(Path::new(path)).unwrap()));
+    let record = flag_value(&args, "--record").map(PathBuf::from);
+    let load = flag_value(&args, "--load").map(|path| Save::load(Path::new(path)).unwrap());
+
+    // A replay needs as many players as it was recorded with
+    let players = playback.as_ref().map_or_else(
+        || flag_value(&args, "--players").map_or(1, |players| players.parse().expect("--players needs a number")),
+        |playback| playback.players()
+    );
+
+    // Two players can share the whole window or have half each
+    let split = args.iter().any(|arg| arg == "--split");
+
+    // A server runs the game for everyone playing over the network, and clients connect to it to play
+    if let Some(address) = flag_value(&args, "--server") {
+        return run_server(address);
+    }
+
+    let connect = flag_value(&args, "--connect");
+
+    if args.iter().any(|arg| arg == "--headless") {
+        // Replays start from a seed rather than a save, so a headless run from a save has nothing it could record
+        if load.is_some() && record.is_some() {
+            panic!("--record can't be used with --load when running headless");
+        }
+
+        let ticks = flag_value(&args, "--ticks").map(|ticks| ticks.parse().expect("--ticks needs a number"));
+        return run_headless(ticks, players, playback, record, load);
+    }
+
     let sdl = sdl2::init().unwrap();
     let video = sdl.video().unwrap();
+    let mut pads = Pads::new(sdl.game_controller().unwrap());
 
     let window = video.window("Boxes", WIDTH, HEIGHT)
         .resizable()
@@ -89,88 +441,274 @@ fn main() {
     let mut event_pump = sdl.event_pump().unwrap();
 
     let texture_creator = canvas.texture_creator();
-    let mut ctx = Context::new(canvas, &texture_creator).unwrap();
-
-    let mut rng = rand::thread_rng();
-    let stars = (0 .. 1000).map(|_| (rng.gen_range(0, 1500), rng.gen_range(0, 1000))).collect::<Vec<_>>();
-
-    let mut ships = vec![
-        Ship::new(&mut ctx, vec![
-            Component::new(ComponentType::Metal, -2, -2, Rotation::Up),
-            Component::new(ComponentType::Pipe,  -1, -2, Rotation::Up),
-            Component::new(ComponentType::Metal,  0, -2, Rotation::Up),
-            Component::new(ComponentType::Pipe,   1, -2, Rotation::Up),
-            Component::new(ComponentType::Metal,  2, -2, Rotation::Up),
-            
-            Component::new(ComponentType::Pipe,  -2, -1, Rotation::Right),
-            Component::new(ComponentType::Metal, -1, -1, Rotation::Up),
-            Component::new(ComponentType::Metal,  0, -1, Rotation::Up),
-            Component::new(ComponentType::Metal,  1, -1, Rotation::Up),
-            Component::new(ComponentType::Pipe,   2, -1, Rotation::Right),
-
-            Component::new(ComponentType::hardpoint(WeaponType::Laser), -3, -2, Rotation::Left),
-            Component::new(ComponentType::hardpoint(WeaponType::TractionBeam),  3, -2, Rotation::Right), 
-            Component::new(ComponentType::Metal,  -2,  0, Rotation::Up),
-            Component::new(ComponentType::Metal,  -1,  0, Rotation::Up),
-            Component::new(ComponentType::Reactor, 0,  0, Rotation::Up),
-            Component::new(ComponentType::Metal,   1,  0, Rotation::Up),
-            Component::new(ComponentType::Metal,   2,  0, Rotation::Up),
-
-            Component::new(ComponentType::Pipe,   -2,  1, Rotation::Right),
-            Component::new(ComponentType::Metal,  -1,  1, Rotation::Up),
-            Component::new(ComponentType::Metal,   0,  1, Rotation::Up),
-            Component::new(ComponentType::Metal,   1,  1, Rotation::Up),
-            Component::new(ComponentType::Pipe,    2,  1, Rotation::Right),
-
-            Component::new(ComponentType::Metal,  -2,  2, Rotation::Up),
-            Component::new(ComponentType::Metal,   2,  2, Rotation::Up),
-
-            Component::new(ComponentType::Engine, -2,  3, Rotation::Up),
-            Component::new(ComponentType::Engine, 2,  3, Rotation::Up)
-        ], 100.0, 500.0, 0.5),
-        Ship::new(&mut ctx, vec![
-            Component::new(ComponentType::Rock,  0, -1, Rotation::Up),
-            Component::new(ComponentType::Rock, -1,  0, Rotation::Up),
-            Component::new(ComponentType::Rock,  0,  0, Rotation::Up),
-            Component::new(ComponentType::Rock,  1,  0, Rotation::Up),
-            Component::new(ComponentType::Rock,  0,  1, Rotation::Up),
-            Component::new(ComponentType::Rock,  0,  2, Rotation::Up)
-        ], 500.0, 300.0, 1.0)
-    ];
-
-    let mut controls = Controls::default();
-    let mut rays = Vec::new();
+    let mut ctx = Context::new(Box::new(SdlRenderer::new(canvas, &texture_creator).unwrap()));
+
+    if let Some(address) = connect {
+        return run_client(&mut ctx, &mut event_pump, &mut pads, address);
+    }
+
+    let seed = playback.as_ref().map_or_else(rand::random, |playback| playback.seed());
+    let mut starfield = Starfield::new(seed);
+
+    let ships = create_ships(&mut ctx, players);
+    let mut game = Game::new(&mut ctx, ships, seed);
+
+    let mut recording = Some(Replay::new(seed, players));
+
+    if let Some(save) = load {
+        game.clear(&mut ctx);
+        let (loaded, loaded_starfield) = save.restore(&mut ctx);
+        game = loaded;
+        starfield = loaded_starfield;
+        // Replays start from a seed rather than a save, so there's nothing to play back or record
+        playback = None;
+        stop_recording(&mut recording, record.as_ref(), "a save was loaded");
+    }
+
+    let mut controls = create_controls(players);
+    let mut editor = Editor::new(&Path::new(SHIPS_DIR).join(format!("{}.ship", PLAYER_SHIP)));
+    let mut clock = Clock::new(DT);
+
+    // A camera for each view, which is swapped into the context while the view is drawn
+    let views = if split { players } else { 1 };
+    let mut cameras = (0 .. views).map(|_| Camera::new(WIDTH as f32, HEIGHT as f32)).collect::<Vec<_>>();
 
     'main: loop {
         for event in event_pump.poll_iter() {
+            if pads.handle_event(&event, &mut controls) {
+                continue;
+            }
+
             match event {
                 Event::Quit {..} => break 'main,
-                Event::KeyDown {keycode: Some(key), ..} => controls.handle_key(key, true),
-                Event::KeyUp {keycode: Some(key), ..} => controls.handle_key(key, false),
-                Event::MouseMotion {x, y, ..} => controls.move_mouse(x, y),
-                Event::MouseButtonDown {mouse_btn: MouseButton::Left, ..} => controls.handle_mouse_down(),
-                Event::MouseButtonUp   {mouse_btn: MouseButton::Left, ..} => controls.handle_mouse_up(),
+                Event::KeyDown {keycode: Some(Keycode::F3), ..} => ctx.debug = !ctx.debug,
+                Event::KeyDown {keycode: Some(Keycode::Tab), ..} => {
+                    editor.toggle();
+
+                    // Edits to the ship aren't part of the input, so a replay can't play back past them
+                    if editor.active {
+                        stop_recording(&mut recording, record.as_ref(), "the editor was opened");
+                    }
+                },
+                Event::KeyDown {keycode: Some(Keycode::F6), ..} => match saves::save(&game, &starfield, Path::new(SAVE_FILE)) {
+                    Ok(()) => println!("Saved to {}", SAVE_FILE),
+                    Err(err) => println!("Couldn't save: {}", err)
+                },
+                Event::KeyDown {keycode: Some(Keycode::F9), ..} => match Save::load(Path::new(SAVE_FILE)) {
+                    Ok(save) => {
+                        game.clear(&mut ctx);
+                        let (loaded, loaded_starfield) = save.restore(&mut ctx);
+                        game = loaded;
+                        starfield = loaded_starfield;
+                        // Replays start from a seed rather than a save, so there's nothing left to play back or record
+                        playback = None;
+                        stop_recording(&mut recording, record.as_ref(), "a save was loaded");
+                    },
+                    Err(err) => println!("Couldn't load: {}", err)
+                },
+                Event::KeyDown {keycode: Some(Keycode::P), ..} => clock.toggle_pause(),
+                Event::KeyDown {keycode: Some(Keycode::Period), ..} => clock.step_once(),
+                Event::KeyDown {keycode: Some(Keycode::LeftBracket), ..} => clock.slow_down(),
+                Event::KeyDown {keycode: Some(Keycode::RightBracket), ..} => clock.speed_up(),
+                Event::KeyDown {keycode: Some(key), ..} if editor.active => {
+                    if let Some(ship) = game.player_ship(0) {
+                        editor.handle_key(key, &mut game.ships[ship], &mut ctx);
+                    }
+                },
+                Event::KeyDown {keycode: Some(key), ..} => controls.iter_mut().for_each(|controls| controls.handle_key(key, true)),
+                Event::KeyUp {keycode: Some(key), ..} => controls.iter_mut().for_each(|controls| controls.handle_key(key, false)),
+                // The mouse belongs to the first player, whose view is always at the top left of the window
+                Event::MouseMotion {x, y, ..} => controls[0].move_mouse(x, y, &cameras[0]),
+                Event::MouseWheel {y, ..} => cameras.iter_mut().for_each(|camera| camera.zoom_by(y)),
+                Event::MouseButtonDown {mouse_btn: MouseButton::Left, ..} => controls[0].handle_mouse_down(),
+                Event::MouseButtonDown {mouse_btn: MouseButton::Right, ..} => controls[0].handle_right_mouse(true),
+                Event::MouseButtonUp   {mouse_btn: MouseButton::Right, ..} => controls[0].handle_right_mouse(false),
+                Event::MouseButtonUp   {mouse_btn: MouseButton::Left, ..} => {
+                    if editor.active {
+                        if let (Some(selection), Some(ship)) = (controls[0].selection.as_ref(), game.player_ship(0)) {
+                            editor.select(selection, &mut game.ships[ship], &mut ctx);
+                        }
+                    }
+
+                    controls[0].handle_mouse_up();
+                },
                 _ => {}
             }
         }
 
-        ctx.step(1.0 / 60.0);
+        // Run as many fixed ticks as the time since the last frame covers, however fast frames are coming
+        let taken = player_damage(&game, players);
+
+        for _ in 0 .. clock.ticks() {
+            let inputs = player_inputs(&game, &mut controls, editor.active);
+            tick(&mut ctx, &mut game, inputs, &mut recording, &mut playback);
+
+            // Stop at the end of a replay so what happened can be looked at, before handing over to the players
+            if playback.as_ref().map_or(false, |playback| playback.len() == game.ticks) {
+                println!("Replay finished after {} ticks", game.ticks);
+                clock.pause();
+                break;
+            }
+        }
+
+        // How much damage each player's ship took, to shake their view by
+        let damage = taken.iter()
+            .zip(player_damage(&game, players))
+            .map(|(before, after)| match (*before, after) {
+                (Some(before), Some(after)) => (after - before).max(0.0),
+                _ => 0.0
+            })
+            .collect::<Vec<_>>();
 
+        let alpha = clock.alpha();
+        let (width, height) = ctx.window_size();
+
+        ctx.set_viewport(None);
         ctx.set_colour(BLACK);
         ctx.clear();
-        ctx.set_colour(WHITE);
-        stars.iter().for_each(|&(x, y)| ctx.draw_point(x, y));
 
-        ships.iter().for_each(|ship| ship.draw(&mut ctx));
+        for (view, camera) in cameras.iter_mut().enumerate() {
+            // Split views follow their own player, and a shared one follows everyone
+            let followed = if split { view .. view + 1 } else { 0 .. players };
+            let view_width = width / views as u32;
+            ctx.set_viewport(if split { Some((view as i32 * view_width as i32, 0, view_width, height)) } else { None });
+            mem::swap(&mut ctx.camera, camera);
 
-        ships[0].step(&controls, &mut ctx, &mut rays);
+            let positions = followed.clone()
+                .filter_map(|player| game.player_ship(player))
+                .map(|ship| game.ships[ship].interpolate(alpha).translation.vector)
+                .collect::<Vec<_>>();
 
-        rays.iter_mut().for_each(|ray| ray.intersect(&mut ships, &mut ctx));
-        rays.iter().for_each(|ray| ray.draw(&mut ctx));
-        rays.clear();
+            // Aim between the followed ships and zoom out far enough to keep them all in view
+            let target = if positions.is_empty() {
+                ctx.camera.centre()
+            } else {
+                let (min_x, max_x) = positions.iter().fold((f32::MAX, f32::MIN), |(min, max), position| (min.min(position.x), max.max(position.x)));
+                let (min_y, max_y) = positions.iter().fold((f32::MAX, f32::MIN), |(min, max), position| (min.min(position.y), max.max(position.y)));
 
-        controls.selection.iter().for_each(|sel| sel.draw(&mut ctx, &ships[0]));
+                // A lone ship doesn't need any room kept around it
+                if positions.len() > 1 {
+                    ctx.camera.fit(max_x - min_x + FIT_MARGIN, max_y - min_y + FIT_MARGIN);
+                } else {
+                    ctx.camera.fit(0.0, 0.0);
+                }
+
+                ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0)
+            };
+
+            ctx.camera.shake(followed.clone().map(|player| damage[player]).fold(0.0, |shake: f32, damage| shake.max(damage)) * SHAKE_PER_DAMAGE);
+            ctx.update_camera(target, clock.frame_time());
+
+            if view == 0 {
+                controls[0].update_mouse(&ctx.camera);
+            }
+
+            starfield.draw(&mut ctx);
+            game.draw(&mut ctx, alpha);
+            controls.iter().for_each(|controls| controls.draw_aim(&mut ctx));
+
+            // The first player's ship is the one that gets selected and edited
+            if let (0, Some(ship)) = (view, game.player_ship(0)) {
+                controls[0].selection.iter().for_each(|sel| sel.draw(&mut ctx, &game.ships[ship]));
+
+                if editor.active {
+                    editor.draw(&mut ctx, &game.ships[ship]);
+                }
+            }
+
+            // Each player's HUD goes at the left of their part of the window
+            let hud_width = ctx.view_size().0 as f32 / followed.len() as f32;
+
+            for (i, player) in followed.enumerate() {
+                if let Some(ship) = game.player_ship(player) {
+                    hud::draw(&mut ctx, &game.ships[ship], controls[player].assist(), i as f32 * hud_width);
+                }
+            }
+
+            mem::swap(&mut ctx.camera, camera);
+        }
+
+        // Divide the views with a line
+        ctx.set_viewport(None);
+        ctx.set_colour(WHITE);
+
+        for view in 1 .. views {
+            let x = (width / views as u32 * view as u32) as f32;
+            ctx.draw_line_screen(x, 0.0, x, height as f32);
+        }
 
         ctx.present();
     }
+
+    if let (Some(path), Some(recording)) = (record, recording) {
+        recording.save(&path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use replays::checksum;
+
+    // Fly forwards in bursts and keep the first weapon group firing to the right
+    fn flying(tick: u32) -> Vec<Option<Input>> {
+        let mut input = Input {
+            up: tick % 40 < 20,
+            left: tick % 90 < 10,
+            ..Input::default()
+        };
+
+        input.fire[0] = true;
+        input.aim_direction = Some((1.0, 0.0));
+        vec![Some(input)]
+    }
+
+    // Run a new game without a window, recording it and playing back a replay if there is one
+    fn run(seed: u32, ticks: u32, playback: &mut Option<Playback>) -> (Game, Replay) {
+        let mut ctx = Context::headless();
+        let ships = create_ships(&mut ctx, 1);
+        let mut game = Game::new(&mut ctx, ships, seed);
+        let mut recording = Some(Replay::new(seed, 1));
+
+        for _ in 0 .. ticks {
+            let inputs = flying(game.ticks);
+            tick(&mut ctx, &mut game, inputs, &mut recording, playback);
+        }
+
+        (game, recording.unwrap())
+    }
+
+    #[test]
+    fn headless_ticks() {
+        let (game, replay) = run(1, 120, &mut None);
+
+        assert_eq!(game.ticks, 120);
+        assert_eq!(replay.len(), 120);
+        assert!(game.player_ship(0).is_some());
+
+        for ship in &game.ships {
+            let (x, y) = ship.position();
+            assert!(x.is_finite() && y.is_finite());
+        }
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let (first, _) = run(5, 120, &mut None);
+        let (second, _) = run(5, 120, &mut None);
+
+        assert_eq!(checksum(&first.ships), checksum(&second.ships));
+    }
+
+    #[test]
+    fn replay_plays_back() {
+        let (recorded, replay) = run(9, 180, &mut None);
+        let replay = Replay::parse(&replay.to_string()).unwrap();
+
+        let mut playback = Some(Playback::new(replay));
+        let (played, _) = run(9, 180, &mut playback);
+
+        assert_eq!(playback.unwrap().diverged, None);
+        assert_eq!(checksum(&played.ships), checksum(&recorded.ships));
+    }
 }
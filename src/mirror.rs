@@ -0,0 +1,198 @@
+use nalgebra::{Vector1, Vector2, Isometry2};
+
+use std::collections::{HashMap, VecDeque};
+
+use context::Context;
+use controllers::Input;
+use ships::{Ship, Component, Motion};
+use weapons::{Shots, wrap_angle};
+use blueprints::cell;
+use network::Snapshot;
+
+// How many ticks behind the newest snapshot other ships are shown, so there's usually a snapshot either side to move between
+const INTERPOLATION_DELAY: f32 = 4.0;
+// How many snapshots of where each ship was are kept
+const HISTORY: usize = 8;
+
+// Whether two ships have the same parts in the same places, even if their health or ammo or aim is different
+fn same_layout(components: &[Component], other: &[Component]) -> bool {
+    components.len() == other.len() && components.iter().zip(other).all(|(a, b)| {
+        a.x == b.x && a.y == b.y && cell(&a.tag, &a.rotation) == cell(&b.tag, &b.rotation)
+    })
+}
+
+// Where a ship was part way through its history, held still so it doesn't drift away from there between ticks
+fn interpolate(history: &VecDeque<(u32, Motion)>, time: f32) -> Option<Motion> {
+    let (position, centre) = match history.iter().position(|&(tick, _)| tick as f32 >= time) {
+        Some(i) if i > 0 => {
+            let (before_tick, ref before) = history[i - 1];
+            let (after_tick, ref after) = history[i];
+            let alpha = (time - before_tick as f32) / (after_tick - before_tick) as f32;
+            let translation = before.position.translation.vector + (after.position.translation.vector - before.position.translation.vector) * alpha;
+            let rotation = before.position.rotation.arg() + wrap_angle(after.position.rotation.arg() - before.position.rotation.arg()) * alpha;
+            (Isometry2::new(translation, rotation), after.centre)
+        },
+        Some(i) => (history[i].1.position, history[i].1.centre),
+        None => match history.back() {
+            Some(&(_, ref motion)) => (motion.position, motion.centre),
+            None => return None
+        }
+    };
+
+    Some(Motion {
+        position, centre,
+        lin_vel: Vector2::new(0.0, 0.0),
+        ang_vel: Vector1::new(0.0)
+    })
+}
+
+// The world as a client sees it. The player's own ship runs ahead on their input and is put right whenever a snapshot
+// says where it really was, while everything else is shown a little in the past, moving smoothly between snapshots.
+// Loose ore isn't sent, so it isn't shown
+pub struct Mirror {
+    pub ships: Vec<Ship>,
+    shots: Shots,
+    // Where each ship was in recent snapshots, by its id
+    histories: HashMap<u32, VecDeque<(u32, Motion)>>,
+    // The newest server tick heard about, and the one everyone else is being shown at
+    latest: u32,
+    time: f32,
+    player: usize
+}
+
+impl Mirror {
+    pub fn new(player: usize) -> Self {
+        Self {
+            ships: Vec::new(),
+            shots: Shots::default(),
+            histories: HashMap::new(),
+            latest: 0,
+            time: 0.0,
+            player
+        }
+    }
+
+    // The index of the player's own ship, once it's in a snapshot
+    pub fn own_ship(&self) -> Option<usize> {
+        self.ships.iter().position(|ship| ship.player == Some(self.player))
+    }
+
+    // Bring the world up to date with a snapshot, then run the inputs the server hadn't got to yet on top of the player's ship
+    pub fn apply(&mut self, ctx: &mut Context, snapshot: Snapshot, pending: &VecDeque<(u32, Input)>, dt: f32) {
+        if snapshot.tick <= self.latest {
+            return;
+        }
+
+        if self.latest == 0 {
+            self.time = (snapshot.tick as f32 - INTERPOLATION_DELAY).max(0.0);
+        }
+
+        self.latest = snapshot.tick;
+        self.shots = snapshot.shots;
+
+        // Ships that aren't in the snapshot have been destroyed or left behind
+        let ids = snapshot.ships.iter().map(|&(id, _)| id).collect::<Vec<_>>();
+        let (kept, gone): (Vec<_>, Vec<_>) = self.ships.drain(..).partition(|ship| ids.contains(&ship.id));
+        self.ships = kept;
+
+        for ship in gone {
+            ctx.remove_rigid_body(&ship.handle);
+            self.histories.remove(&ship.id);
+        }
+
+        for (id, saved) in snapshot.ships {
+            let motion = saved.motion.clone();
+
+            match self.ships.iter().position(|ship| ship.id == id) {
+                // Only what's kept in the parts has changed, like their health, ammo and which way turrets point
+                Some(i) if same_layout(&self.ships[i].components, &saved.components) => {
+                    let ship = &mut self.ships[i];
+                    ship.player = saved.player;
+
+                    for (component, saved) in ship.components.iter_mut().zip(saved.components) {
+                        component.set_health(saved.health());
+                        component.tag = saved.tag;
+                    }
+                },
+                // Parts have been knocked off or added, so the ship needs a new body, which keeps flying with the old one's assist
+                Some(i) => {
+                    ctx.remove_rigid_body(&self.ships[i].handle);
+                    let assist = self.ships[i].assist.clone();
+                    self.ships[i] = saved.restore(ctx);
+                    self.ships[i].id = id;
+                    self.ships[i].assist = assist;
+                },
+                None => {
+                    let mut ship = saved.restore(ctx);
+                    ship.id = id;
+                    self.ships.push(ship);
+                }
+            }
+
+            let history = self.histories.entry(id).or_insert_with(VecDeque::new);
+            history.push_back((snapshot.tick, motion));
+
+            while history.len() > HISTORY {
+                history.pop_front();
+            }
+        }
+
+        if let Some(i) = self.own_ship() {
+            let motion = self.histories[&self.ships[i].id].back().map(|&(_, ref motion)| motion.clone());
+
+            if let Some(motion) = motion {
+                self.ships[i].set_motion(&motion);
+            }
+
+            // Shots fired while catching up have already been fired on the server, and flight assist has already
+            // been run through these ticks, so it's put back to where it had got to rather than run through them twice
+            let mut shots = Shots::default();
+            let assist = self.ships[i].assist.clone();
+
+            for &(_, ref input) in pending {
+                self.ships[i].start_tick();
+                self.ships[i].step(input, &mut shots);
+                ctx.step(dt);
+            }
+
+            self.ships[i].assist = assist;
+        }
+    }
+
+    // Fly the player's ship on their input straight away, and move everything else on towards the newest snapshot
+    pub fn tick(&mut self, ctx: &mut Context, input: Option<Input>, dt: f32) {
+        self.ships.iter_mut().for_each(|ship| ship.start_tick());
+
+        let own = self.own_ship();
+
+        if let (Some(i), Some(input)) = (own, input) {
+            self.ships[i].step(&input, &mut Shots::default());
+        }
+
+        ctx.step(dt);
+
+        // Fall back in behind if the snapshots have got too far ahead, like after a stall
+        self.time = (self.time + 1.0).min(self.latest as f32);
+
+        if self.latest as f32 - self.time > INTERPOLATION_DELAY * 2.0 {
+            self.time = self.latest as f32 - INTERPOLATION_DELAY;
+        }
+
+        let (histories, time) = (&self.histories, self.time);
+
+        for (i, ship) in self.ships.iter_mut().enumerate() {
+            if Some(i) != own {
+                if let Some(motion) = histories.get(&ship.id).and_then(|history| interpolate(history, time)) {
+                    ship.set_motion(&motion);
+                }
+            }
+        }
+
+        self.shots.projectiles.iter_mut().for_each(|projectile| projectile.drift(dt));
+    }
+
+    pub fn draw(&self, ctx: &mut Context, alpha: f32) {
+        self.ships.iter().for_each(|ship| ship.draw(ctx, alpha));
+        self.shots.draw(ctx, alpha);
+    }
+}
@@ -0,0 +1,597 @@
+use nalgebra::{Point2, Vector2};
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, UdpSocket};
+
+use controllers::Input;
+use game::Game;
+use projectiles::Projectile;
+use replays::{input_values, parse_input};
+use saves::{SavedShip, ship_values, part_values, parse_part};
+use weapons::{Shots, WeaponRay, WeaponType};
+
+// Messages are text sent one to a datagram, with a directive on each line like saves and replays.
+// Clients send:
+//
+//   join                           Asking for a ship, which is sent again until the server answers
+//   input <tick> <input...>        The input for one of the client's ticks, as in replays. The last few are sent each tick
+//                                  so one datagram going missing doesn't lose any
+//   leave
+//
+// The server sends:
+//
+//   welcome <player> <starfield>   The player number the client's ship is flown by, and the starfield seed
+//   full                           There's no room for another player
+//   snapshot <tick> <ack> <piece> <pieces>
+//                                  The world after one of the server's ticks, and the last of the client's ticks it has used.
+//                                  The world is split into as many pieces as it takes to fit each in a datagram, and each
+//                                  piece is followed by its share of:
+//   ship <id> <values...>          Every ship, with the values and parts as in saves, which are never split from their ship
+//   part <values...>
+//   shot <weapon> <x> <y> <velocity x> <velocity y>
+//   ray <weapon> <start x> <start y> <end x> <end y>
+
+// The most players a server takes
+const MAX_PLAYERS: usize = 8;
+// How many ticks the server waits to hear from a client before giving up on it
+const TIMEOUT: u32 = 300;
+// How many of its latest inputs a client sends each tick
+const REDUNDANCY: usize = 3;
+// How many inputs the server holds for a client before skipping ahead, so the client's ship doesn't fall further and further behind
+const MAX_QUEUED: usize = 6;
+// How many inputs a client keeps waiting for the server to use, in case it has stopped answering
+const MAX_PENDING: usize = 120;
+// The largest datagram UDP can carry
+const MAX_DATAGRAM: usize = 65_507;
+// How much of a datagram is kept for the line that starts each piece of a snapshot
+const HEADER_ROOM: usize = 64;
+// How many ticks older than the newest piece to arrive a snapshot can be and still be waited on for its missing pieces
+const PIECE_TIMEOUT: u32 = 30;
+
+fn parse_number<T: ::std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse().map_err(|_| format!("'{}' is not a valid number", word))
+}
+
+fn parse_weapon(word: &str) -> Result<WeaponType, String> {
+    WeaponType::from_name(word).ok_or_else(|| format!("unknown weapon '{}'", word))
+}
+
+// Check a directive has the right number of values
+fn expect_args(directive: &str, args: &[&str], count: usize) -> Result<(), String> {
+    if args.len() == count {
+        Ok(())
+    } else {
+        Err(format!("'{}' takes {} values, got {}", directive, count, args.len()))
+    }
+}
+
+// Split a line into its directive and values, skipping blank lines
+fn words(line: &str) -> Option<(&str, Vec<&str>)> {
+    let mut words = line.split_whitespace();
+    words.next().map(|directive| (directive, words.collect()))
+}
+
+// Read datagrams until there aren't any more waiting, returning each as text along with who sent it
+fn receive_all(socket: &UdpSocket) -> Vec<(String, SocketAddr)> {
+    let mut buffer = vec![0; MAX_DATAGRAM];
+    let mut received = Vec::new();
+
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((length, address)) => received.push((String::from_utf8_lossy(&buffer[.. length]).into_owned(), address)),
+            Err(ref err) if err.kind() == ErrorKind::WouldBlock => break,
+            // Sending to a port nobody is listening on can come back as an error on the next receive, which isn't a problem here
+            Err(ref err) if err.kind() == ErrorKind::ConnectionRefused || err.kind() == ErrorKind::ConnectionReset => continue,
+            Err(err) => {
+                println!("Couldn't receive: {}", err);
+                break;
+            }
+        }
+    }
+
+    received
+}
+
+// The whole world after a tick, as the lines of a snapshot after the first, with each ship and its parts kept together
+fn write_world(game: &Game) -> Vec<String> {
+    let mut entries = Vec::new();
+
+    for ship in &game.ships {
+        let mut text = format!("ship {} {}\n", ship.id, ship_values(ship));
+
+        for component in &ship.components {
+            text += &format!("part {}\n", part_values(component));
+        }
+
+        entries.push(text);
+    }
+
+    for projectile in &game.shots.projectiles {
+        let (position, velocity) = (projectile.position(), projectile.velocity());
+        entries.push(format!("shot {} {} {} {} {}\n", projectile.tag().name(), position.x, position.y, velocity.x, velocity.y));
+    }
+
+    for ray in &game.shots.rays {
+        let (start, end) = ray.ends();
+        entries.push(format!("ray {} {} {} {} {}\n", ray.tag().name(), start.x, start.y, end.x, end.y));
+    }
+
+    entries
+}
+
+// Gather the world into pieces that each fit in a datagram along with the line that starts them
+fn split_world(entries: Vec<String>) -> Vec<String> {
+    let mut pieces = vec![String::new()];
+
+    for entry in entries {
+        if pieces.last().map_or(false, |piece| !piece.is_empty() && piece.len() + entry.len() > MAX_DATAGRAM - HEADER_ROOM) {
+            pieces.push(String::new());
+        }
+
+        if let Some(piece) = pieces.last_mut() {
+            piece.push_str(&entry);
+        }
+    }
+
+    pieces
+}
+
+// The world as the server saw it after a tick
+pub struct Snapshot {
+    pub tick: u32,
+    // The last of the client's ticks whose input had been used
+    pub ack: u32,
+    // Every ship along with its id
+    pub ships: Vec<(u32, SavedShip)>,
+    pub shots: Shots
+}
+
+impl Snapshot {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().filter_map(words);
+
+        let (tick, ack) = match lines.next() {
+            Some(("snapshot", ref args)) if args.len() == 2 => (parse_number(args[0])?, parse_number(args[1])?),
+            _ => return Err("snapshots have to start with 'snapshot <tick> <ack>'".into())
+        };
+
+        let mut snapshot = Self {
+            tick, ack,
+            ships: Vec::new(),
+            shots: Shots::default()
+        };
+
+        for (directive, args) in lines {
+            match directive {
+                "ship" => {
+                    if args.is_empty() {
+                        return Err("'ship' needs an id".into());
+                    }
+
+                    snapshot.ships.push((parse_number(args[0])?, SavedShip::parse(&args[1..])?));
+                },
+                "part" => {
+                    let component = parse_part(&args)?;
+
+                    match snapshot.ships.last_mut() {
+                        Some(&mut (_, ref mut ship)) => ship.components.push(component),
+                        None => return Err("'part' has to come after a 'ship'".into())
+                    }
+                },
+                "shot" => {
+                    expect_args(directive, &args, 5)?;
+                    let position = Point2::new(parse_number(args[1])?, parse_number(args[2])?);
+                    let velocity = Vector2::new(parse_number(args[3])?, parse_number(args[4])?);
+                    snapshot.shots.projectiles.push(Projectile::new(parse_weapon(args[0])?, position, velocity, 1));
+                },
+                "ray" => {
+                    expect_args(directive, &args, 5)?;
+                    let start = Point2::new(parse_number(args[1])?, parse_number(args[2])?);
+                    let end = Point2::new(parse_number(args[3])?, parse_number(args[4])?);
+                    snapshot.shots.rays.push(WeaponRay::between(parse_weapon(args[0])?, start, end));
+                },
+                _ => return Err(format!("unknown directive '{}'", directive))
+            }
+        }
+
+        Ok(snapshot)
+    }
+}
+
+// A player joining or leaving the server
+pub enum ClientEvent {
+    Joined(usize),
+    Left(usize)
+}
+
+// A client as the server sees it
+struct Remote {
+    address: SocketAddr,
+    player: usize,
+    // Inputs that have arrived but haven't been used yet, oldest first
+    queue: VecDeque<(u32, Input)>,
+    // The newest of the client's ticks heard about, so inputs that arrive late or twice are ignored
+    received: u32,
+    // The input used in the last tick, which carries on if the next one is late, and the client tick it was for
+    last: Option<(u32, Input)>,
+    // Ticks since the client was last heard from
+    silent: u32
+}
+
+// Runs the game for clients to play over UDP. The server's simulation is the one that counts,
+// and clients only send what their players are doing
+pub struct Server {
+    socket: UdpSocket,
+    remotes: Vec<Remote>,
+    starfield: u32
+}
+
+impl Server {
+    pub fn bind(address: &str, starfield: u32) -> io::Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            remotes: Vec::new(),
+            starfield
+        })
+    }
+
+    pub fn players(&self) -> Vec<usize> {
+        self.remotes.iter().map(|remote| remote.player).collect()
+    }
+
+    fn send(&self, address: SocketAddr, text: &str) {
+        if text.len() > MAX_DATAGRAM {
+            println!("Couldn't send {} bytes to {}, which is more than a datagram can carry", text.len(), address);
+            return;
+        }
+
+        if let Err(err) = self.socket.send_to(text.as_bytes(), address) {
+            println!("Couldn't send to {}: {}", address, err);
+        }
+    }
+
+    // Read everything clients have sent since the last tick, returning who has joined and left
+    pub fn receive(&mut self) -> Vec<ClientEvent> {
+        let mut events = Vec::new();
+
+        for (text, address) in receive_all(&self.socket) {
+            if let Err(err) = self.handle(&text, address, &mut events) {
+                println!("Bad message from {}: {}", address, err);
+            }
+        }
+
+        for remote in &mut self.remotes {
+            remote.silent += 1;
+        }
+
+        // Clients that have gone quiet have probably gone away without saying so
+        while let Some(i) = self.remotes.iter().position(|remote| remote.silent > TIMEOUT) {
+            let remote = self.remotes.remove(i);
+            println!("{} timed out", remote.address);
+            events.push(ClientEvent::Left(remote.player));
+        }
+
+        events
+    }
+
+    fn handle(&mut self, text: &str, address: SocketAddr, events: &mut Vec<ClientEvent>) -> Result<(), String> {
+        for (directive, args) in text.lines().filter_map(words) {
+            let remote = self.remotes.iter().position(|remote| remote.address == address);
+
+            if let Some(i) = remote {
+                self.remotes[i].silent = 0;
+            }
+
+            match (directive, remote) {
+                // The welcome went missing, so send it again
+                ("join", Some(i)) => {
+                    let player = self.remotes[i].player;
+                    self.send(address, &format!("welcome {} {}", player, self.starfield));
+                },
+                ("join", None) => {
+                    let players = self.players();
+
+                    match (0 .. MAX_PLAYERS).find(|player| !players.contains(player)) {
+                        Some(player) => {
+                            self.remotes.push(Remote {
+                                address, player,
+                                queue: VecDeque::new(),
+                                received: 0,
+                                last: None,
+                                silent: 0
+                            });
+
+                            println!("{} joined as player {}", address, player);
+                            events.push(ClientEvent::Joined(player));
+                            self.send(address, &format!("welcome {} {}", player, self.starfield));
+                        },
+                        None => self.send(address, "full")
+                    }
+                },
+                ("leave", Some(i)) => {
+                    let remote = self.remotes.remove(i);
+                    println!("{} left", remote.address);
+                    events.push(ClientEvent::Left(remote.player));
+                },
+                ("input", Some(i)) => {
+                    if args.is_empty() {
+                        return Err("'input' needs a tick".into());
+                    }
+
+                    let tick = parse_number(args[0])?;
+                    let input = parse_input(&args[1..])?;
+                    let remote = &mut self.remotes[i];
+
+                    if tick > remote.received {
+                        remote.received = tick;
+                        remote.queue.push_back((tick, input));
+                    }
+                },
+                // Anything else from someone who hasn't joined, like input after being timed out, is ignored
+                ("leave", None) | ("input", None) => {},
+                _ => return Err(format!("unknown directive '{}'", directive))
+            }
+        }
+
+        Ok(())
+    }
+
+    // Each player's input for the next tick, which is the next one each client sent, or the last one again if it hasn't arrived
+    pub fn inputs(&mut self) -> Vec<Option<Input>> {
+        let mut inputs = vec![None; MAX_PLAYERS];
+
+        for remote in &mut self.remotes {
+            while remote.queue.len() > MAX_QUEUED {
+                remote.queue.pop_front();
+            }
+
+            if let Some(next) = remote.queue.pop_front() {
+                remote.last = Some(next);
+            }
+
+            inputs[remote.player] = remote.last.as_ref().map(|&(_, ref input)| input.clone());
+        }
+
+        inputs
+    }
+
+    // Send every client the whole world, along with how far through its input the server has got
+    pub fn send_snapshot(&self, game: &Game) {
+        let pieces = split_world(write_world(game));
+
+        for remote in &self.remotes {
+            let ack = remote.last.as_ref().map_or(0, |&(tick, _)| tick);
+
+            for (i, piece) in pieces.iter().enumerate() {
+                self.send(remote.address, &format!("snapshot {} {} {} {}\n{}", game.ticks, ack, i, pieces.len(), piece));
+            }
+        }
+    }
+}
+
+// A client's connection to a server
+pub struct Connection {
+    socket: UdpSocket,
+    // The player number and starfield seed the server gave, once it has answered
+    pub player: Option<usize>,
+    pub starfield: Option<u32>,
+    // How many ticks of input have been sent
+    tick: u32,
+    // Inputs that have been sent but that the server hasn't used yet, oldest first
+    pending: VecDeque<(u32, Input)>,
+    // The pieces of snapshots that have arrived so far, by tick, until every piece of one is in or it gets too old,
+    // and the newest tick any piece has been for
+    pieces: HashMap<u32, Vec<Option<String>>>,
+    latest: u32
+}
+
+impl Connection {
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(address)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            player: None,
+            starfield: None,
+            tick: 0,
+            pending: VecDeque::new(),
+            pieces: HashMap::new(),
+            latest: 0
+        })
+    }
+
+    fn send(&self, text: &str) {
+        match self.socket.send(text.as_bytes()) {
+            Ok(_) => {},
+            // The server isn't there yet
+            Err(ref err) if err.kind() == ErrorKind::ConnectionRefused => {},
+            Err(err) => println!("Couldn't send: {}", err)
+        }
+    }
+
+    pub fn join(&self) {
+        self.send("join");
+    }
+
+    pub fn leave(&self) {
+        self.send("leave");
+    }
+
+    pub fn pending(&self) -> &VecDeque<(u32, Input)> {
+        &self.pending
+    }
+
+    // Send the input for the next tick, along with the last few in case any of them went missing
+    pub fn send_input(&mut self, input: Input) {
+        self.tick += 1;
+        self.pending.push_back((self.tick, input));
+
+        while self.pending.len() > MAX_PENDING {
+            self.pending.pop_front();
+        }
+
+        let text = self.pending.iter()
+            .skip(self.pending.len().saturating_sub(REDUNDANCY))
+            .map(|&(tick, ref input)| format!("input {} {}\n", tick, input_values(input)))
+            .collect::<String>();
+
+        self.send(&text);
+    }
+
+    // Keep a piece of a snapshot with the others from its tick, returning the whole snapshot once every piece is in
+    fn assemble(&mut self, args: &[&str], text: &str) -> Result<Option<Snapshot>, String> {
+        expect_args("snapshot", args, 4)?;
+        let (tick, ack) = (parse_number::<u32>(args[0])?, parse_number::<u32>(args[1])?);
+        let (piece, count) = (parse_number::<usize>(args[2])?, parse_number::<usize>(args[3])?);
+
+        if piece >= count {
+            return Err(format!("piece {} of a snapshot with {} pieces", piece, count));
+        }
+
+        {
+            let pieces = self.pieces.entry(tick).or_insert_with(|| vec![None; count]);
+
+            if pieces.len() != count {
+                return Err(format!("snapshot {} was sent in {} pieces, not {}", tick, pieces.len(), count));
+            }
+
+            pieces[piece] = Some(text.splitn(2, '\n').nth(1).unwrap_or("").to_string());
+        }
+
+        // Give up on snapshots whose missing pieces have most likely been lost, which may be this one
+        self.latest = self.latest.max(tick);
+        let latest = self.latest;
+        self.pieces.retain(|&other, _| latest - other <= PIECE_TIMEOUT);
+
+        let world = match self.pieces.get(&tick).and_then(|pieces| pieces.iter().cloned().collect::<Option<String>>()) {
+            Some(world) => world,
+            None => return Ok(None)
+        };
+
+        // Older snapshots still missing pieces would be thrown away for this one anyway
+        self.pieces.retain(|&other, _| other > tick);
+
+        Snapshot::parse(&format!("snapshot {} {}\n{}", tick, ack, world)).map(Some)
+    }
+
+    // Read everything the server has sent, returning the newest snapshot if there were any
+    pub fn receive(&mut self) -> Option<Snapshot> {
+        let mut newest: Option<Snapshot> = None;
+
+        for (text, _) in receive_all(&self.socket) {
+            match text.lines().filter_map(words).next() {
+                Some(("welcome", ref args)) if args.len() == 2 => match (args[0].parse(), args[1].parse()) {
+                    (Ok(player), Ok(starfield)) => {
+                        self.player = Some(player);
+                        self.starfield = Some(starfield);
+                    },
+                    _ => println!("Bad welcome from the server")
+                },
+                Some(("full", _)) => println!("The server is full"),
+                Some(("snapshot", ref args)) => match self.assemble(args, &text) {
+                    Ok(Some(snapshot)) => {
+                        if newest.as_ref().map_or(true, |newest| snapshot.tick > newest.tick) {
+                            newest = Some(snapshot);
+                        }
+                    },
+                    Ok(None) => {},
+                    Err(err) => println!("Bad snapshot from the server: {}", err)
+                },
+                _ => println!("Unknown message from the server")
+            }
+        }
+
+        // The server has used everything up to the snapshot's input, so that isn't needed to catch up any more
+        if let Some(ref snapshot) = newest {
+            while self.pending.front().map_or(false, |&(tick, _)| tick <= snapshot.ack) {
+                self.pending.pop_front();
+            }
+        }
+
+        newest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assist::Assist;
+
+    const SHOT: &str = "shot cannon 1 2 3 4\n";
+    const RAY: &str = "ray laser 0 0 5 5\n";
+
+    // Hand a connection one piece of a snapshot as if it had come from the server
+    fn receive_piece(connection: &mut Connection, tick: u32, piece: usize, count: usize, world: &str) -> Result<Option<Snapshot>, String> {
+        let text = format!("snapshot {} 0 {} {}\n{}", tick, piece, count, world);
+        let (_, args) = words(text.lines().next().unwrap()).unwrap();
+        connection.assemble(&args, &text)
+    }
+
+    #[test]
+    fn big_worlds_are_split_to_fit() {
+        let entries = vec![SHOT.to_string(); 10_000];
+        let pieces = split_world(entries.clone());
+
+        assert!(pieces.len() > 1);
+        assert!(pieces.iter().all(|piece| piece.len() <= MAX_DATAGRAM - HEADER_ROOM));
+        assert!(pieces.iter().all(|piece| piece.ends_with('\n')));
+        assert_eq!(pieces.concat(), entries.concat());
+    }
+
+    #[test]
+    fn empty_worlds_still_send_a_piece() {
+        assert_eq!(split_world(Vec::new()), vec![String::new()]);
+    }
+
+    #[test]
+    fn snapshots_wait_for_every_piece() {
+        let mut connection = Connection::connect("127.0.0.1:9").unwrap();
+
+        assert!(receive_piece(&mut connection, 5, 1, 2, RAY).unwrap().is_none());
+        let snapshot = receive_piece(&mut connection, 5, 0, 2, SHOT).unwrap().unwrap();
+
+        assert_eq!(snapshot.tick, 5);
+        assert_eq!(snapshot.shots.projectiles.len(), 1);
+        assert_eq!(snapshot.shots.rays.len(), 1);
+        assert!(connection.pieces.is_empty());
+    }
+
+    #[test]
+    fn snapshots_missing_pieces_are_given_up_on() {
+        let mut connection = Connection::connect("127.0.0.1:9").unwrap();
+
+        assert!(receive_piece(&mut connection, 1, 0, 2, SHOT).unwrap().is_none());
+        assert!(receive_piece(&mut connection, 2 + PIECE_TIMEOUT, 0, 2, SHOT).unwrap().is_none());
+        assert!(!connection.pieces.contains_key(&1));
+
+        // The rest of it turning up late doesn't bring it back
+        assert!(receive_piece(&mut connection, 1, 1, 2, RAY).unwrap().is_none());
+        assert!(!connection.pieces.contains_key(&1));
+        assert!(connection.pieces.contains_key(&(2 + PIECE_TIMEOUT)));
+    }
+
+    #[test]
+    fn inputs_survive_being_sent() {
+        let mut input = Input {
+            left: true,
+            throttle: 0.75,
+            assist: Assist::PointAtAim,
+            aim_direction: Some((-1.0, 0.0)),
+            ..Input::default()
+        };
+
+        input.fire[3] = true;
+        input.aims[0] = (-3.5, 800.0);
+
+        let values = input_values(&input);
+        let parsed = parse_input(&values.split_whitespace().collect::<Vec<_>>()).unwrap();
+
+        assert_eq!(input_values(&parsed), values);
+    }
+}
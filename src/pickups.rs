@@ -0,0 +1,120 @@
+use nphysics2d::object::{RigidBody, RigidBodyHandle};
+use ncollide::shape::Ball;
+use ncollide::query::{Ray, RayCast};
+use nalgebra::{Vector2, Point2};
+use ord_subset::OrdSubsetIterExt;
+use rand::Rng;
+
+use std::f32::consts::PI;
+
+use context::Context;
+use resources::Image;
+use ships::{Ship, SIZE};
+use random::CountingRng;
+
+const RADIUS: f32 = 6.0;
+// How much ore each destroyed rock drops
+const ORE_PER_ROCK: u32 = 5;
+// How fast ore scatters from the rock it came out of
+const SCATTER_SPEED: f32 = 30.0;
+// Seconds before ore that nobody collected disappears
+const LIFETIME: f32 = 60.0;
+// How close ore has to get to a collector to be absorbed
+const COLLECT_DISTANCE: f32 = SIZE;
+
+// A loose piece of ore floating around that collectors can pick up
+pub struct Pickup {
+    pub handle: RigidBodyHandle<f32>,
+    pub amount: u32,
+    age: f32
+}
+
+impl Pickup {
+    fn position(&self) -> Point2<f32> {
+        Point2::from_coordinates(self.handle.borrow().position().translation.vector)
+    }
+}
+
+pub struct Pickups {
+    pub pickups: Vec<Pickup>,
+    // Seeded so that where ore scatters is the same every time a game is replayed
+    seed: u32,
+    rng: CountingRng
+}
+
+impl Pickups {
+    pub fn new(seed: u32) -> Self {
+        Self::resume(seed, 0)
+    }
+
+    // Pickups carrying on from a saved game, like AsteroidField::resume
+    pub fn resume(seed: u32, draws: u64) -> Self {
+        Self {
+            pickups: Vec::new(),
+            seed,
+            // Xorshift can't be seeded with all zeroes
+            rng: CountingRng::resume([0x6c07_8965, seed, 0x2545_f491, 0x9e37_79b9], draws)
+        }
+    }
+
+    // The seed and how many numbers have been drawn from it, like AsteroidField::seed
+    pub fn seed(&self) -> (u32, u64) {
+        (self.seed, self.rng.draws())
+    }
+
+    // Drop the ore from a destroyed rock, moving along with it and scattering a little
+    pub fn drop_ore(&mut self, ctx: &mut Context, position: Vector2<f32>, velocity: Vector2<f32>) {
+        let angle = self.rng.gen_range(0.0, 2.0 * PI);
+        let handle = ctx.add_rigid_body(RigidBody::new_dynamic(Ball::new(RADIUS), 1.0, 0.5, 1.0), position.x, position.y, angle);
+
+        handle.borrow_mut().set_lin_vel(velocity + Vector2::new(angle.cos(), angle.sin()) * SCATTER_SPEED);
+
+        self.pickups.push(Pickup {
+            handle,
+            amount: ORE_PER_ROCK,
+            age: 0.0
+        });
+    }
+
+    // Find the pickup a ray hits first, returning its index, the point hit and the time of impact along the ray
+    pub fn cast(&self, ray: &Ray<Point2<f32>>) -> Option<(usize, Point2<f32>, f32)> {
+        self.pickups.iter().enumerate()
+            .filter_map(|(i, pickup)| {
+                let rigid_body = pickup.handle.borrow();
+                rigid_body.shape().as_ref().toi_with_ray(rigid_body.position(), ray, true)
+                    .map(|toi| (i, ray.origin + ray.dir * toi, toi))
+            })
+            .ord_subset_min_by_key(|&(_, _, toi)| toi)
+    }
+
+    // Let collectors take any ore that's close enough, and clear away ore that has been floating around too long
+    pub fn step(&mut self, dt: f32, ships: &mut [Ship], ctx: &mut Context) {
+        for pickup in &mut self.pickups {
+            pickup.age += dt;
+            let position = pickup.position();
+
+            for ship in ships.iter_mut() {
+                if pickup.amount == 0 {
+                    break;
+                }
+
+                pickup.amount -= ship.collect(position, pickup.amount, COLLECT_DISTANCE);
+            }
+        }
+
+        for pickup in self.pickups.iter().filter(|pickup| pickup.amount == 0 || pickup.age > LIFETIME) {
+            ctx.remove_rigid_body(&pickup.handle);
+        }
+
+        self.pickups.retain(|pickup| pickup.amount > 0 && pickup.age <= LIFETIME);
+    }
+
+    pub fn draw(&self, ctx: &mut Context) {
+        for pickup in &self.pickups {
+            let rigid_body = pickup.handle.borrow();
+            let position = rigid_body.position();
+            let size = RADIUS * 2.0;
+            ctx.draw_rotated(&Image::Ore, position.translation.vector.x, position.translation.vector.y, size, size, position.rotation.arg().to_degrees());
+        }
+    }
+}
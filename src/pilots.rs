@@ -0,0 +1,171 @@
+use nalgebra::Vector2;
+
+use controllers::{Controller, Input};
+use ships::{Ship, ComponentType};
+use weapons::{wrap_angle, GROUPS};
+
+// How far from its target a pilot circles, and how far away it starts closing in instead
+const ORBIT_RADIUS: f32 = 400.0;
+const SEEK_DISTANCE: f32 = 700.0;
+// How close a target has to be before the pilot opens fire
+const FIRE_RANGE: f32 = 900.0;
+// The speed the pilot tries to fly at relative to its target
+const MAX_SPEED: f32 = 200.0;
+// The fraction of its starting health below which the pilot runs away
+const FLEE_HEALTH: f32 = 0.3;
+// How closely the nose has to point where the pilot wants to go before it fires the main engines, in radians
+const THRUST_ANGLE: f32 = 0.3;
+// How far ahead in seconds the pilot allows for its spin when turning, so it doesn't overshoot
+const SPIN_LOOKAHEAD: f32 = 0.5;
+// How many times to refine where to aim, as the aim point changes how long shots take to get there
+const LEAD_ITERATIONS: usize = 3;
+
+#[derive(Clone, Copy)]
+enum Behaviour {
+    Seek,
+    Orbit,
+    Flee
+}
+
+// Where a ship is, which way it faces, how fast it's moving and how fast it's spinning
+fn state(ship: &Ship) -> (Vector2<f32>, f32, Vector2<f32>, f32) {
+    let rigid_body = ship.handle.borrow();
+    let position = rigid_body.position();
+    (position.translation.vector, position.rotation.arg(), rigid_body.lin_vel(), rigid_body.ang_vel().x)
+}
+
+// Roughly how much damage a ship's powered weapons could do each tick
+fn firepower(ship: &Ship) -> f32 {
+    ship.components.iter()
+        .filter_map(|component| match component.tag {
+            ComponentType::Hardpoint(ref weapon) => {
+                let tag = weapon.tag();
+                Some(tag.damage_profile().damage() * component.power / tag.firing_model().interval as f32)
+            },
+            _ => None
+        })
+        .sum()
+}
+
+// Where to aim so a shot at a speed meets a target, allowing for the shot inheriting the shooter's velocity
+fn lead(origin: Vector2<f32>, velocity: Vector2<f32>, target: Vector2<f32>, target_velocity: Vector2<f32>, speed: f32) -> Vector2<f32> {
+    let relative = target_velocity - velocity;
+    let mut aim = target;
+
+    for _ in 0 .. LEAD_ITERATIONS {
+        let time = (aim - origin).norm() / speed;
+        aim = target + relative * time;
+    }
+
+    aim
+}
+
+// A computer pilot that picks the most threatening ship to fight and runs when badly damaged
+pub struct Pilot {
+    // The ship's health when the pilot first flew it
+    full_health: Option<f32>
+}
+
+impl Pilot {
+    pub fn new() -> Self {
+        Self {
+            full_health: None
+        }
+    }
+
+    // The ship posing the most danger, from how hard it hits and how close it is
+    fn target(&self, ship: usize, ships: &[Ship]) -> Option<usize> {
+        let (position, _, _, _) = state(&ships[ship]);
+
+        ships.iter().enumerate()
+            .filter(|&(i, other)| i != ship && !other.is_asteroid())
+            .map(|(i, other)| {
+                let distance = (state(other).0 - position).norm().max(1.0);
+                (i, firepower(other) / distance)
+            })
+            .filter(|&(_, threat)| threat > 0.0)
+            .fold(None, |best: Option<(usize, f32)>, (i, threat)| match best {
+                Some((_, best_threat)) if best_threat >= threat => best,
+                _ => Some((i, threat))
+            })
+            .map(|(i, _)| i)
+    }
+}
+
+impl Controller for Pilot {
+    fn input(&mut self, ship: usize, ships: &[Ship]) -> Input {
+        let mut input = Input::default();
+
+        let target = match self.target(ship, ships) {
+            Some(target) => target,
+            None => return input
+        };
+
+        let health = ships[ship].health();
+        let full_health = *self.full_health.get_or_insert(health);
+
+        let (position, angle, velocity, spin) = state(&ships[ship]);
+        let (target_position, _, target_velocity, _) = state(&ships[target]);
+        let offset = target_position - position;
+        let distance = offset.norm().max(1.0);
+        let towards = offset / distance;
+
+        let behaviour = if health < full_health * FLEE_HEALTH {
+            Behaviour::Flee
+        } else if distance > SEEK_DISTANCE {
+            Behaviour::Seek
+        } else {
+            Behaviour::Orbit
+        };
+
+        // The velocity the pilot wants relative to its target
+        let desired = match behaviour {
+            Behaviour::Seek => towards,
+            // Circle round the target, drifting in or out towards the orbit
+            Behaviour::Orbit => {
+                let around = Vector2::new(-towards.y, towards.x) + towards * (distance - ORBIT_RADIUS) / ORBIT_RADIUS;
+                around / around.norm()
+            },
+            Behaviour::Flee => -towards
+        } * MAX_SPEED + target_velocity;
+
+        // Ships face up, so a heading of zero points along negative y
+        let steer = desired - velocity;
+        let heading = steer.x.atan2(-steer.y);
+        let error = wrap_angle(heading - angle - spin * SPIN_LOOKAHEAD);
+
+        if error.abs() < THRUST_ANGLE {
+            input.up = true;
+        } else if error > 0.0 {
+            input.right = true;
+        } else {
+            input.left = true;
+        }
+
+        if distance > FIRE_RANGE {
+            return input;
+        }
+
+        for group in 0 .. GROUPS {
+            let weapons = ships[ship].components.iter()
+                .filter_map(|component| match component.tag {
+                    ComponentType::Hardpoint(ref weapon) if weapon.group() == group => Some(weapon.tag()),
+                    _ => None
+                })
+                .collect::<Vec<_>>();
+
+            // Leave groups with nothing that does damage, like traction beams, alone
+            input.fire[group] = weapons.iter().any(|tag| tag.damage_profile().damage() > 0.0);
+
+            // Lead with the group's projectiles, or aim straight at the target with beams
+            let aim = match weapons.iter().filter_map(|tag| tag.ballistics()).next() {
+                Some(ballistics) => lead(position, velocity, target_position, target_velocity, ballistics.speed),
+                None => target_position
+            };
+
+            input.aims[group] = (aim.x, aim.y);
+        }
+
+        input
+    }
+}
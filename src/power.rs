@@ -0,0 +1,132 @@
+use ships::{Component, ComponentType, Rotation};
+
+// The grid offsets of the up, right, down and left sides of a component
+const SIDES: [(i8, i8); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+// Which sides of a component power can pass through, in the same order as SIDES
+fn openings(component: &Component) -> [bool; 4] {
+    match component.tag {
+        // Pipes run left to right when facing up or down, and top to bottom when facing left or right
+        ComponentType::Pipe => match component.rotation {
+            Rotation::Up | Rotation::Down => [false, true, false, true],
+            Rotation::Right | Rotation::Left => [true, false, true, false]
+        },
+        ComponentType::Reactor | ComponentType::Engine | ComponentType::Hardpoint(_) => [true; 4],
+        _ => [false; 4]
+    }
+}
+
+// Split the components into networks of reactors and pipes, and give each consumer the fraction of its demand that its network can supply
+pub fn distribute(components: &mut [Component]) {
+    for component in components.iter_mut() {
+        component.power = 0.0;
+    }
+
+    let mut visited = vec![false; components.len()];
+
+    for start in 0 .. components.len() {
+        if visited[start] || !components[start].tag.conducts() {
+            continue;
+        }
+
+        visited[start] = true;
+        let mut network = vec![start];
+        let mut consumers = Vec::new();
+        let mut i = 0;
+
+        // Flood fill through every conductor that has a matching opening on the other side
+        while i < network.len() {
+            let (x, y, open) = (components[network[i]].x, components[network[i]].y, openings(&components[network[i]]));
+
+            for (side, &(dx, dy)) in SIDES.iter().enumerate().filter(|&(side, _)| open[side]) {
+                let next = components.iter().position(|component| component.x == x.wrapping_add(dx) && component.y == y.wrapping_add(dy));
+
+                if let Some(next) = next {
+                    if visited[next] || !openings(&components[next])[(side + 2) % 4] {
+                        continue;
+                    }
+
+                    visited[next] = true;
+
+                    if components[next].tag.conducts() {
+                        network.push(next);
+                    } else {
+                        consumers.push(next);
+                    }
+                }
+            }
+
+            i += 1;
+        }
+
+        let supply = network.iter().map(|&i| components[i].tag.power_output()).sum::<f32>();
+        let demand = consumers.iter().map(|&i| components[i].tag.power_demand()).sum::<f32>();
+        let satisfaction = if demand > 0.0 { (supply / demand).min(1.0) } else { 1.0 };
+
+        for &i in &consumers {
+            components[i].power = satisfaction;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn powers(components: &mut [Component]) -> Vec<f32> {
+        distribute(components);
+        components.iter().map(|component| component.power).collect()
+    }
+
+    #[test]
+    fn reactor_powers_its_neighbours() {
+        let mut components = vec![
+            Component::new(ComponentType::Reactor, 0, 0, Rotation::Up),
+            Component::new(ComponentType::Engine, 0, 1, Rotation::Up),
+            Component::new(ComponentType::Engine, 1, 0, Rotation::Up)
+        ];
+
+        assert_eq!(powers(&mut components), vec![0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn demand_beyond_supply_is_shared() {
+        let mut components = vec![
+            Component::new(ComponentType::Reactor, 0, 0, Rotation::Up),
+            Component::new(ComponentType::Engine, 0, -1, Rotation::Up),
+            Component::new(ComponentType::Engine, 1, 0, Rotation::Up),
+            Component::new(ComponentType::Engine, 0, 1, Rotation::Up)
+        ];
+
+        let expected = ComponentType::Reactor.power_output() / (3.0 * ComponentType::Engine.power_demand());
+        assert!(expected < 1.0);
+
+        for &power in &powers(&mut components)[1..] {
+            assert!((power - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn pipes_only_carry_power_along_their_length() {
+        // A pipe facing up runs left to right, so the engine above it gets nothing
+        let mut components = vec![
+            Component::new(ComponentType::Reactor, 0, 0, Rotation::Up),
+            Component::new(ComponentType::Pipe, 1, 0, Rotation::Up),
+            Component::new(ComponentType::Engine, 2, 0, Rotation::Up),
+            Component::new(ComponentType::Engine, 1, -1, Rotation::Up)
+        ];
+
+        assert_eq!(powers(&mut components), vec![0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn metal_blocks_power() {
+        let mut components = vec![
+            Component::new(ComponentType::Reactor, 0, 0, Rotation::Up),
+            Component::new(ComponentType::Metal, 1, 0, Rotation::Up),
+            Component::new(ComponentType::Engine, 2, 0, Rotation::Up)
+        ];
+
+        assert_eq!(powers(&mut components), vec![0.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,88 @@
+use nalgebra::{Vector2, Point2};
+use ncollide::query::Ray;
+
+use context::Context;
+use ships::Ship;
+use pickups::Pickups;
+use weapons::{self, WeaponType};
+
+// How many ticks of travel a projectile's tracer shows
+const TRACER_LENGTH: f32 = 1.0 / 30.0;
+
+pub struct Projectile {
+    tag: WeaponType,
+    position: Point2<f32>,
+    // Where it was before the last tick, for drawing in between ticks
+    previous: Point2<f32>,
+    velocity: Vector2<f32>,
+    // How far it has flown, for damage falloff
+    travelled: f32,
+    // Ticks left before it burns out
+    lifetime: u32
+}
+
+impl Projectile {
+    pub fn new(tag: WeaponType, position: Point2<f32>, velocity: Vector2<f32>, lifetime: u32) -> Self {
+        Self {
+            tag, position, velocity, lifetime,
+            previous: position,
+            travelled: 0.0
+        }
+    }
+
+    // Move the projectile along, damaging the first ship in its path, and return if it's still flying
+    pub fn step(&mut self, dt: f32, ships: &mut Vec<Ship>, pickups: &mut Pickups, ctx: &mut Context) -> bool {
+        self.previous = self.position;
+
+        // Self-propelled projectiles speed up along their direction of travel
+        if let Some(ballistics) = self.tag.ballistics() {
+            let speed = self.velocity.norm();
+
+            if speed > 0.0 {
+                self.velocity += self.velocity / speed * ballistics.thrust * dt;
+            }
+        }
+
+        // Sweep along the path for this tick, so fast projectiles can't skip through thin ships
+        let travel = self.velocity * dt;
+        let ray = Ray::new(self.position, travel);
+
+        if let Some((i, point, toi)) = weapons::cast(&ray, ships) {
+            if toi <= 1.0 {
+                let damage = self.tag.damage_profile().at(self.travelled + travel.norm() * toi);
+                weapons::hit(ships, i, point, &damage, pickups, ctx);
+                return false;
+            }
+        }
+
+        self.position += travel;
+        self.travelled += travel.norm();
+        self.lifetime = self.lifetime.saturating_sub(1);
+        self.lifetime > 0
+    }
+
+    pub fn tag(&self) -> WeaponType {
+        self.tag
+    }
+
+    pub fn position(&self) -> Point2<f32> {
+        self.position
+    }
+
+    pub fn velocity(&self) -> Vector2<f32> {
+        self.velocity
+    }
+
+    // Move the projectile along without hitting anything, for shots that are only being shown
+    pub fn drift(&mut self, dt: f32) {
+        self.previous = self.position;
+        self.position += self.velocity * dt;
+    }
+
+    pub fn draw(&self, ctx: &mut Context, alpha: f32) {
+        let head = self.previous + (self.position - self.previous) * alpha;
+        let tail = head - self.velocity * TRACER_LENGTH;
+        ctx.set_colour(self.tag.colour());
+        ctx.draw_line(tail.x, tail.y, head.x, head.y);
+    }
+}
@@ -0,0 +1,37 @@
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+// A seeded generator that counts the numbers it gives, so it can be saved as its seed and that count
+// and pick up exactly where it left off
+#[derive(Clone)]
+pub struct CountingRng {
+    rng: XorShiftRng,
+    draws: u64
+}
+
+impl CountingRng {
+    // A generator from a seed that has already given some numbers, which are drawn again and thrown away
+    pub fn resume(seed: [u32; 4], draws: u64) -> Self {
+        let mut rng = XorShiftRng::from_seed(seed);
+
+        for _ in 0 .. draws {
+            rng.next_u32();
+        }
+
+        Self {
+            rng, draws
+        }
+    }
+
+    // How many numbers have been drawn since the seed
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+}
+
+// Every other way of drawing numbers is built on this one, so they're all counted
+impl Rng for CountingRng {
+    fn next_u32(&mut self) -> u32 {
+        self.draws += 1;
+        self.rng.next_u32()
+    }
+}
@@ -0,0 +1,114 @@
+use sdl2::render::{TextureCreator, WindowCanvas};
+use sdl2::rect::Rect;
+use sdl2::render::BlendMode;
+use sdl2::video::WindowContext;
+use sdl2::pixels::Color;
+
+use std::error::Error;
+
+use colours::BLACK;
+use resources::{Resources, Image};
+
+// Everything that draws to the screen goes through this, so the simulation can run without a window
+pub trait Renderer {
+    fn set_colour(&mut self, colour: Color);
+    fn draw_point(&mut self, x: i32, y: i32);
+    fn draw_line(&mut self, x_1: f32, y_1: f32, x_2: f32, y_2: f32);
+    fn draw_rotated(&mut self, image: &Image, x: f32, y: f32, width: f32, height: f32, rotation: f32);
+    // Draw an image with its colours multiplied by a tint
+    fn draw_tinted(&mut self, image: &Image, x: f32, y: f32, width: f32, height: f32, rotation: f32, tint: Color);
+    // Draw into a part of the screen, with coordinates relative to its top left, or all of it with None
+    fn set_viewport(&mut self, viewport: Option<(i32, i32, u32, u32)>);
+    fn clear(&mut self);
+    fn present(&mut self);
+    // The size of the drawing area in pixels
+    fn size(&self) -> (u32, u32);
+}
+
+pub struct SdlRenderer<'a> {
+    canvas: WindowCanvas,
+    resources: Resources<'a>
+}
+
+impl<'a> SdlRenderer<'a> {
+    pub fn new(mut canvas: WindowCanvas, texture_creator: &'a TextureCreator<WindowContext>) -> Result<Self, Box<Error>> {
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(BLACK);
+
+        Ok(Self {
+            resources: Resources::new(texture_creator)?,
+            canvas
+        })
+    }
+
+    fn to_rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect::new((x - width / 2.0).round() as i32, (y - height / 2.0).round() as i32, width.round() as u32 + 1, height.round() as u32 + 1)
+    }
+}
+
+impl<'a> Renderer for SdlRenderer<'a> {
+    fn set_colour(&mut self, colour: Color) {
+        self.canvas.set_draw_color(colour);
+    }
+
+    fn draw_point(&mut self, x: i32, y: i32) {
+        self.canvas.draw_point((x, y)).unwrap();
+    }
+
+    fn draw_line(&mut self, x_1: f32, y_1: f32, x_2: f32, y_2: f32) {
+        self.canvas.draw_line((x_1 as i32, y_1 as i32), (x_2 as i32, y_2 as i32)).unwrap();
+    }
+
+    fn draw_rotated(&mut self, image: &Image, x: f32, y: f32, width: f32, height: f32, rotation: f32) {
+        self.canvas.copy_ex(
+            self.resources.texture(image),
+            None, Some(Self::to_rect(x, y, width, height)),
+            f64::from(rotation), None,
+            false, false
+        ).unwrap();
+    }
+
+    fn draw_tinted(&mut self, image: &Image, x: f32, y: f32, width: f32, height: f32, rotation: f32, tint: Color) {
+        self.resources.texture_mut(image).set_color_mod(tint.r, tint.g, tint.b);
+        self.draw_rotated(image, x, y, width, height, rotation);
+        self.resources.texture_mut(image).set_color_mod(255, 255, 255);
+    }
+
+    fn set_viewport(&mut self, viewport: Option<(i32, i32, u32, u32)>) {
+        self.canvas.set_viewport(viewport.map(|(x, y, width, height)| Rect::new(x, y, width, height)));
+        self.canvas.set_clip_rect(viewport.map(|(_, _, width, height)| Rect::new(0, 0, width, height)));
+    }
+
+    fn clear(&mut self) {
+        self.canvas.clear();
+    }
+
+    fn present(&mut self) {
+        self.canvas.present();
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.canvas.output_size().unwrap()
+    }
+}
+
+// A renderer that throws everything away, for running headless
+pub struct NullRenderer;
+
+// The size a headless renderer pretends to be
+const NULL_SIZE: (u32, u32) = (1280, 800);
+
+impl Renderer for NullRenderer {
+    fn set_colour(&mut self, _: Color) {}
+    fn draw_point(&mut self, _: i32, _: i32) {}
+    fn draw_line(&mut self, _: f32, _: f32, _: f32, _: f32) {}
+    fn draw_rotated(&mut self, _: &Image, _: f32, _: f32, _: f32, _: f32, _: f32) {}
+    fn draw_tinted(&mut self, _: &Image, _: f32, _: f32, _: f32, _: f32, _: f32, _: Color) {}
+    fn set_viewport(&mut self, _: Option<(i32, i32, u32, u32)>) {}
+    fn clear(&mut self) {}
+    fn present(&mut self) {}
+
+    fn size(&self) -> (u32, u32) {
+        NULL_SIZE
+    }
+}
@@ -0,0 +1,379 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use controllers::Input;
+use ships::Ship;
+use weapons::GROUPS;
+use assist::Assist;
+
+// Replays are text with one directive per line and `#` comments:
+//
+//   version <number>       The format version, which has to come first
+//   seed <number>          The seed the game was started with
+//   players <number>       How many players there were
+//   tick                   The start of a tick, followed by each player's input in turn
+//   idle                   A player had no input, like while their ship was gone
+//   input <up> <left> <right> <throttle> <assist> <fire...> <aim x> <aim y>... <direction x> <direction y>
+//                          A player's input, with 1 or 0 for each key and weapon group, how hard they thrust, which flight assist mode
+//                          was on, where each group aimed and the direction everything aimed along, or 0 0 when there wasn't one
+//   checksum <tick> <hex>  A checksum of every ship's position after a tick, to spot playback going differently
+
+const VERSION: u32 = 6;
+// How many ticks apart checksums are taken
+const CHECKSUM_INTERVAL: u32 = 60;
+
+// A hash of where every ship is and how it's moving, which should come out the same on every playback
+pub fn checksum(ships: &[Ship]) -> u64 {
+    // 64 bit FNV-1a
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+
+    for ship in ships {
+        let rigid_body = ship.handle.borrow();
+        let position = rigid_body.position();
+        let lin_vel = rigid_body.lin_vel();
+
+        let values = [
+            position.translation.vector.x, position.translation.vector.y, position.rotation.arg(),
+            lin_vel.x, lin_vel.y, rigid_body.ang_vel().x
+        ];
+
+        for value in &values {
+            let bits = value.to_bits();
+
+            for shift in &[0, 8, 16, 24] {
+                hash ^= u64::from((bits >> shift) as u8);
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        }
+    }
+
+    hash
+}
+
+fn flag(value: bool) -> &'static str {
+    if value { "1" } else { "0" }
+}
+
+fn parse_flag(word: &str) -> Result<bool, String> {
+    match word {
+        "1" => Ok(true),
+        "0" => Ok(false),
+        _ => Err(format!("expected 1 or 0, got '{}'", word))
+    }
+}
+
+fn parse_number<T: ::std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse().map_err(|_| format!("'{}' is not a valid number", word))
+}
+
+// Check a directive has the right number of values
+fn expect_args(directive: &str, args: &[&str], count: usize) -> Result<(), String> {
+    if args.len() == count {
+        Ok(())
+    } else {
+        Err(format!("'{}' takes {} values, got {}", directive, count, args.len()))
+    }
+}
+
+// The values of an 'input' directive, which are also how input is sent over the network
+pub fn input_values(input: &Input) -> String {
+    let mut values = format!("{} {} {} {} {}", flag(input.up), flag(input.left), flag(input.right), input.throttle, input.assist.name());
+
+    for fire in &input.fire {
+        values += &format!(" {}", flag(*fire));
+    }
+
+    for &(x, y) in &input.aims {
+        values += &format!(" {} {}", x, y);
+    }
+
+    let (x, y) = input.aim_direction.unwrap_or((0.0, 0.0));
+    values += &format!(" {} {}", x, y);
+
+    values
+}
+
+pub fn parse_input(args: &[&str]) -> Result<Input, String> {
+    expect_args("input", args, 7 + GROUPS * 3)?;
+
+    let mut input = Input {
+        up: parse_flag(args[0])?,
+        left: parse_flag(args[1])?,
+        right: parse_flag(args[2])?,
+        throttle: parse_number(args[3])?,
+        assist: Assist::from_name(args[4]).ok_or_else(|| format!("unknown flight assist mode '{}'", args[4]))?,
+        ..Input::default()
+    };
+
+    for group in 0 .. GROUPS {
+        input.fire[group] = parse_flag(args[5 + group])?;
+
+        let aim = &args[5 + GROUPS + group * 2 ..];
+        input.aims[group] = (parse_number(aim[0])?, parse_number(aim[1])?);
+    }
+
+    let direction = &args[5 + GROUPS * 3 ..];
+    let (x, y) = (parse_number(direction[0])?, parse_number(direction[1])?);
+
+    if (x, y) != (0.0, 0.0) {
+        input.aim_direction = Some((x, y));
+    }
+
+    Ok(input)
+}
+
+// The seed and player input of a game, which is enough to play it out again exactly
+pub struct Replay {
+    pub seed: u32,
+    pub players: usize,
+    // Each player's input for each tick
+    inputs: Vec<Vec<Option<Input>>>,
+    // Checksums of the ships after some of the ticks, along with the tick
+    checksums: Vec<(u32, u64)>
+}
+
+impl Replay {
+    pub fn new(seed: u32, players: usize) -> Self {
+        Self {
+            seed,
+            players,
+            inputs: Vec::new(),
+            checksums: Vec::new()
+        }
+    }
+
+    // The number of ticks in the replay
+    pub fn len(&self) -> u32 {
+        self.inputs.len() as u32
+    }
+
+    // Add the players' input for the next tick
+    pub fn record(&mut self, inputs: &[Option<Input>]) {
+        self.inputs.push(inputs.to_vec());
+    }
+
+    // Take a checksum of the ships every so often, after the tick has been run
+    pub fn record_checksum(&mut self, tick: u32, ships: &[Ship]) {
+        if tick % CHECKSUM_INTERVAL == 0 {
+            self.checksums.push((tick, checksum(ships)));
+        }
+    }
+
+    // The players' input for a tick
+    pub fn inputs(&self, tick: u32) -> Vec<Option<Input>> {
+        self.inputs.get(tick as usize).cloned().unwrap_or_default()
+    }
+
+    // Whether the ships match the recorded checksum after a tick, if one was taken then
+    pub fn check(&self, tick: u32, ships: &[Ship]) -> bool {
+        self.checksums.iter()
+            .find(|&&(checked, _)| checked == tick)
+            .map_or(true, |&(_, expected)| checksum(ships) == expected)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<Error>> {
+        let mut replay = Self::new(0, 1);
+        let mut version: Option<u32> = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let (directive, args) = (words[0], &words[1..]);
+
+            let result = if directive == "version" {
+                expect_args(directive, args, 1).and_then(|_| parse_number(args[0])).map(|number| version = Some(number))
+            } else if version.is_none() {
+                Err("replay has to start with its version".to_string())
+            } else {
+                match directive {
+                    "seed" => expect_args(directive, args, 1).and_then(|_| parse_number(args[0])).map(|seed| replay.seed = seed),
+                    "players" => expect_args(directive, args, 1).and_then(|_| parse_number(args[0])).map(|players| replay.players = players),
+                    "tick" => expect_args(directive, args, 0).map(|_| replay.inputs.push(Vec::new())),
+                    "idle" => expect_args(directive, args, 0).and_then(|_| replay.add_input(None)),
+                    "input" => parse_input(args).and_then(|input| replay.add_input(Some(input))),
+                    "checksum" => expect_args(directive, args, 2)
+                        .and_then(|_| parse_number(args[0]))
+                        .and_then(|tick| u64::from_str_radix(args[1], 16)
+                            .map(|hash| replay.checksums.push((tick, hash)))
+                            .map_err(|_| format!("'{}' is not a valid checksum", args[1]))),
+                    _ => Err(format!("unknown directive '{}'", directive))
+                }
+            };
+
+            result.map_err(|message| format!("line {}: {}", i + 1, message))?;
+        }
+
+        match version {
+            Some(VERSION) => Ok(replay),
+            Some(version) => Err(format!("replay is version {}, but only version {} can be played", version, VERSION).into()),
+            None => Err("replay is empty".into())
+        }
+    }
+
+    // Add the next player's input to the tick being parsed
+    fn add_input(&mut self, input: Option<Input>) -> Result<(), String> {
+        match self.inputs.last_mut() {
+            Some(tick) => Ok(tick.push(input)),
+            None => Err("input has to come after a 'tick'".into())
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<Error>> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Self::parse(&text).map_err(|err| format!("{}: {}", path.display(), err).into())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<Error>> {
+        File::create(path)?.write_all(self.to_string().as_bytes())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "version {}", VERSION)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "players {}", self.players)?;
+
+        let mut checksums = self.checksums.iter().peekable();
+
+        for (tick, inputs) in self.inputs.iter().enumerate() {
+            writeln!(f, "tick")?;
+
+            for input in inputs {
+                match *input {
+                    Some(ref input) => writeln!(f, "input {}", input_values(input))?,
+                    None => writeln!(f, "idle")?
+                }
+            }
+
+            // Checksums are taken after the tick runs, when the tick count has gone up by one
+            while let Some(&&(checked, hash)) = checksums.peek() {
+                if checked as usize > tick + 1 {
+                    break;
+                }
+
+                writeln!(f, "checksum {} {:016x}", checked, hash)?;
+                checksums.next();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// A replay being played back, which keeps track of whether the game has drifted away from it
+pub struct Playback {
+    replay: Replay,
+    // The first tick where the ships didn't match the recording
+    pub diverged: Option<u32>
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            diverged: None
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.replay.seed
+    }
+
+    pub fn players(&self) -> usize {
+        self.replay.players
+    }
+
+    pub fn len(&self) -> u32 {
+        self.replay.len()
+    }
+
+    // Whether every tick of the replay has been played
+    pub fn finished(&self, tick: u32) -> bool {
+        tick >= self.replay.len()
+    }
+
+    pub fn inputs(&self, tick: u32) -> Vec<Option<Input>> {
+        self.replay.inputs(tick)
+    }
+
+    // Check the ships after a tick against the recording, and return if this is the first time they haven't matched
+    pub fn check(&mut self, tick: u32, ships: &[Ship]) -> bool {
+        if self.diverged.is_none() && !self.replay.check(tick, ships) {
+            self.diverged = Some(tick);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input() -> Input {
+        let mut input = Input {
+            up: true,
+            throttle: 0.5,
+            assist: Assist::HoldVelocity,
+            aim_direction: Some((0.6, -0.8)),
+            ..Input::default()
+        };
+
+        input.fire[1] = true;
+        input.aims[2] = (120.0, -40.5);
+        input
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut replay = Replay::new(42, 2);
+        replay.record(&[Some(input()), None]);
+        replay.record(&[None, Some(Input::default())]);
+        replay.checksums.push((1, 0xdead_beef));
+
+        let parsed = Replay::parse(&replay.to_string()).unwrap();
+
+        assert_eq!((parsed.seed, parsed.players, parsed.len()), (42, 2, 2));
+        assert_eq!(parsed.checksums, vec![(1, 0xdead_beef)]);
+        assert_eq!(parsed.to_string(), replay.to_string());
+
+        let first = parsed.inputs(0);
+        assert_eq!(input_values(first[0].as_ref().unwrap()), input_values(&input()));
+        assert!(first[1].is_none());
+    }
+
+    #[test]
+    fn no_direction_is_none() {
+        let input = Input::default();
+        let values = input_values(&input);
+        let parsed = parse_input(&values.split_whitespace().collect::<Vec<_>>()).unwrap();
+
+        assert!(parsed.aim_direction.is_none());
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let text = format!("version {}\nseed 1\n", VERSION + 1);
+        assert!(Replay::parse(&text).is_err());
+    }
+
+    #[test]
+    fn input_needs_a_tick() {
+        let text = format!("version {}\nidle\n", VERSION);
+        let err = Replay::parse(&text).err().unwrap().to_string();
+
+        assert!(err.starts_with("line 2:"), "{}", err);
+    }
+}
@@ -12,9 +12,17 @@ pub enum Image {
     Reactor,
     Engine,
     Rock,
+    Magazine,
+    Collector,
+    Ore,
     Hardpoint,
     Laser,
-    TractionBeam
+    TractionBeam,
+    Cannon,
+    MissileLauncher,
+    MassDriver,
+    CracksLight,
+    CracksHeavy
 }
 
 macro_rules! load_image {
@@ -32,35 +40,80 @@ pub struct Resources<'a> {
     reactor: Texture<'a>,
     engine: Texture<'a>,
     rock: Texture<'a>,
+    magazine: Texture<'a>,
+    collector: Texture<'a>,
+    ore: Texture<'a>,
     hardpoint: Texture<'a>,
     laser: Texture<'a>,
-    traction_beam: Texture<'a>
+    traction_beam: Texture<'a>,
+    cannon: Texture<'a>,
+    missile_launcher: Texture<'a>,
+    mass_driver: Texture<'a>,
+    cracks_light: Texture<'a>,
+    cracks_heavy: Texture<'a>
 }
 
 impl<'a> Resources<'a> {
     pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Result<Self, Box<Error>> {
         Ok(Self {
-            metal:         load_image!(texture_creator, "metal.png"),
-            pipe:          load_image!(texture_creator, "pipe.png"),
-            reactor:       load_image!(texture_creator, "reactor.png"),
-            engine:        load_image!(texture_creator, "engine.png"),
-            rock:          load_image!(texture_creator, "rock.png"),
-            hardpoint:     load_image!(texture_creator, "hardpoint.png"),
-            laser:         load_image!(texture_creator, "laser.png"),
-            traction_beam: load_image!(texture_creator, "traction_beam.png")
+            metal:            load_image!(texture_creator, "metal.png"),
+            pipe:             load_image!(texture_creator, "pipe.png"),
+            reactor:          load_image!(texture_creator, "reactor.png"),
+            engine:           load_image!(texture_creator, "engine.png"),
+            rock:             load_image!(texture_creator, "rock.png"),
+            magazine:         load_image!(texture_creator, "magazine.png"),
+            collector:        load_image!(texture_creator, "collector.png"),
+            ore:              load_image!(texture_creator, "ore.png"),
+            hardpoint:        load_image!(texture_creator, "hardpoint.png"),
+            laser:            load_image!(texture_creator, "laser.png"),
+            traction_beam:    load_image!(texture_creator, "traction_beam.png"),
+            cannon:           load_image!(texture_creator, "cannon.png"),
+            missile_launcher: load_image!(texture_creator, "missile_launcher.png"),
+            mass_driver:      load_image!(texture_creator, "mass_driver.png"),
+            cracks_light:     load_image!(texture_creator, "cracks_light.png"),
+            cracks_heavy:     load_image!(texture_creator, "cracks_heavy.png")
         })
     }
 
     pub fn texture(&self, image: &Image) -> &Texture<'a> {
         match *image {
-            Image::Metal        => &self.metal,
-            Image::Pipe         => &self.pipe,
-            Image::Reactor      => &self.reactor,
-            Image::Engine       => &self.engine,
-            Image::Rock         => &self.rock,
-            Image::Hardpoint    => &self.hardpoint,
-            Image::Laser        => &self.laser,
-            Image::TractionBeam => &self.traction_beam
+            Image::Metal           => &self.metal,
+            Image::Pipe            => &self.pipe,
+            Image::Reactor         => &self.reactor,
+            Image::Engine          => &self.engine,
+            Image::Rock            => &self.rock,
+            Image::Magazine        => &self.magazine,
+            Image::Collector       => &self.collector,
+            Image::Ore             => &self.ore,
+            Image::Hardpoint       => &self.hardpoint,
+            Image::Laser           => &self.laser,
+            Image::TractionBeam    => &self.traction_beam,
+            Image::Cannon          => &self.cannon,
+            Image::MissileLauncher => &self.missile_launcher,
+            Image::MassDriver      => &self.mass_driver,
+            Image::CracksLight     => &self.cracks_light,
+            Image::CracksHeavy     => &self.cracks_heavy
+        }
+    }
+
+    pub fn texture_mut(&mut self, image: &Image) -> &mut Texture<'a> {
+        match *image {
+            Image::Metal           => &mut self.metal,
+            Image::Pipe            => &mut self.pipe,
+            Image::Reactor         => &mut self.reactor,
+            Image::Engine          => &mut self.engine,
+            Image::Rock            => &mut self.rock,
+            Image::Magazine        => &mut self.magazine,
+            Image::Collector       => &mut self.collector,
+            Image::Ore             => &mut self.ore,
+            Image::Hardpoint       => &mut self.hardpoint,
+            Image::Laser           => &mut self.laser,
+            Image::TractionBeam    => &mut self.traction_beam,
+            Image::Cannon          => &mut self.cannon,
+            Image::MissileLauncher => &mut self.missile_launcher,
+            Image::MassDriver      => &mut self.mass_driver,
+            Image::CracksLight     => &mut self.cracks_light,
+            Image::CracksHeavy     => &mut self.cracks_heavy
         }
     }
 }
\ No newline at end of file
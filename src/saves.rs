@@ -0,0 +1,431 @@
+use nalgebra::{Vector1, Vector2, Point2, Isometry2};
+
+use std::error::Error;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use context::Context;
+use ships::{Ship, Component, ComponentType, Motion};
+use blueprints::{cell, parse_cell};
+use asteroids::AsteroidField;
+use pickups::Pickups;
+use starfield::Starfield;
+use pilots::Pilot;
+use game::{Game, SPAWN_CLEARANCE};
+
+// Saves are text with one directive per line and `#` comments:
+//
+//   version <number>         The format version, which has to come first
+//   starfield <seed>
+//   ticks <number>           How many ticks the game had run for
+//   asteroids <seed> <draws> <count> <radius> <min size> <max size> <density> <max drift> <max spin>
+//   pickups <seed> <draws>   The seed for scattering ore
+//   ship <flown by> <x> <y> <angle> <centre x> <centre y> <velocity x> <velocity y> <spin>
+//   part <cell> <x> <y> <health> [<rounds> | <ore> | <rotation> <charge> <cooldown> <heat> <overheated>]
+//
+// The asteroid field and ore are generated from their seeds, along with how many random numbers had been drawn
+// so generation carries on exactly as it would have.
+// Ships are flown by a player's number, a pilot, or none for wrecks and asteroids.
+// Parts belong to the ship before them, and use the same cells as blueprints, followed by what's
+// left in a magazine or collector or how a hardpoint's turret is turned, how charged it is, how long until it
+// can fire and how hot it is.
+// Shots in flight and loose ore aren't saved.
+
+const VERSION: u32 = 3;
+
+fn flag(value: bool) -> u8 {
+    if value { 1 } else { 0 }
+}
+
+fn parse_flag(word: &str) -> Result<bool, String> {
+    match word {
+        "1" => Ok(true),
+        "0" => Ok(false),
+        _ => Err(format!("expected 1 or 0, got '{}'", word))
+    }
+}
+
+// Parse the arguments of a directive as numbers
+fn parse_numbers<T: ::std::str::FromStr>(directive: &str, args: &[&str], count: usize) -> Result<Vec<T>, String> {
+    if args.len() != count {
+        return Err(format!("'{}' takes {} numbers, got {}", directive, count, args.len()));
+    }
+
+    args.iter()
+        .map(|arg| arg.parse().map_err(|_| format!("'{}' is not a valid number for '{}'", arg, directive)))
+        .collect()
+}
+
+// The values of a 'part' directive, which are also how parts are sent over the network
+pub fn part_values(component: &Component) -> String {
+    let mut values = format!("{} {} {} {}", cell(&component.tag, &component.rotation), component.x, component.y, component.health());
+
+    match component.tag {
+        ComponentType::Magazine(rounds) => values += &format!(" {}", rounds),
+        ComponentType::Collector(ore) => values += &format!(" {}", ore),
+        ComponentType::Hardpoint(ref weapon) => values += &format!(
+            " {} {} {} {} {}", weapon.rotation(), weapon.charge(), weapon.cooldown(), weapon.heat(), flag(weapon.overheated())
+        ),
+        _ => {}
+    }
+
+    values
+}
+
+pub fn parse_part(args: &[&str]) -> Result<Component, String> {
+    if args.len() < 4 {
+        return Err(format!("'part' takes at least 4 values, got {}", args.len()));
+    }
+
+    let (tag, rotation) = parse_cell(args[0])?;
+    let position = parse_numbers::<i8>("part", &args[1 .. 3], 2)?;
+    let health = parse_numbers::<f32>("part", &args[3 .. 4], 1)?[0];
+    let extra = &args[4..];
+
+    let tag = match tag {
+        ComponentType::Magazine(_) => ComponentType::Magazine(parse_numbers::<u32>("part", extra, 1)?[0]),
+        ComponentType::Collector(_) => ComponentType::Collector(parse_numbers::<u32>("part", extra, 1)?[0]),
+        ComponentType::Hardpoint(mut weapon) => {
+            if extra.len() != 5 {
+                return Err(format!("hardpoint parts take 5 more values, got {}", extra.len()));
+            }
+
+            let turret = parse_numbers::<f32>("part", &extra[.. 2], 2)?;
+            let cooldown = parse_numbers::<u32>("part", &extra[2 .. 3], 1)?[0];
+            let heat = parse_numbers::<f32>("part", &extra[3 .. 4], 1)?[0];
+            weapon.restore(turret[0], turret[1], cooldown, heat, parse_flag(extra[4])?);
+            ComponentType::Hardpoint(weapon)
+        },
+        tag => {
+            parse_numbers::<f32>("part", extra, 0)?;
+            tag
+        }
+    };
+
+    let mut component = Component::new(tag, position[0], position[1], rotation);
+    component.set_health(health);
+    Ok(component)
+}
+
+// Write a whole game to a file, without disturbing it so a replay being recorded still plays back
+pub fn save(game: &Game, starfield: &Starfield, path: &Path) -> Result<(), Box<Error>> {
+    let mut text = String::new();
+    let asteroids = &game.asteroids;
+
+    writeln!(text, "version {}", VERSION)?;
+    writeln!(text, "starfield {}", starfield.seed())?;
+    writeln!(text, "ticks {}", game.ticks)?;
+    let (seed, draws) = asteroids.seed();
+    writeln!(
+        text, "asteroids {} {} {} {} {} {} {} {} {}",
+        seed, draws, asteroids.count, asteroids.radius, asteroids.min_size, asteroids.max_size,
+        asteroids.density, asteroids.max_drift, asteroids.max_spin
+    )?;
+
+    let (seed, draws) = game.pickups.seed();
+    writeln!(text, "pickups {} {}", seed, draws)?;
+
+    for ship in &game.ships {
+        writeln!(text)?;
+        writeln!(text, "ship {}", ship_values(ship))?;
+
+        for component in &ship.components {
+            writeln!(text, "part {}", part_values(component))?;
+        }
+    }
+
+    File::create(path)?.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+// The values of a 'ship' directive, which are also how ships are sent over the network
+pub fn ship_values(ship: &Ship) -> String {
+    let motion = ship.motion();
+
+    format!(
+        "{} {} {} {} {} {} {} {} {}",
+        match (ship.player, &ship.pilot) {
+            (Some(player), _) => player.to_string(),
+            (None, &Some(_)) => "pilot".to_string(),
+            (None, &None) => "none".to_string()
+        },
+        motion.position.translation.vector.x, motion.position.translation.vector.y, motion.position.rotation.arg(),
+        motion.centre.x, motion.centre.y, motion.lin_vel.x, motion.lin_vel.y, motion.ang_vel.x
+    )
+}
+
+// A ship read from a save, before it has a body
+pub struct SavedShip {
+    pub player: Option<usize>,
+    pub piloted: bool,
+    pub motion: Motion,
+    pub components: Vec<Component>
+}
+
+impl SavedShip {
+    // Read the values of a 'ship' directive, leaving the parts to be added after
+    pub fn parse(args: &[&str]) -> Result<Self, String> {
+        if args.is_empty() {
+            return Err("'ship' takes 9 values, got 0".into());
+        }
+
+        let values = parse_numbers::<f32>("ship", &args[1..], 8)?;
+
+        Ok(Self {
+            player: match args[0] {
+                "none" | "pilot" => None,
+                player => Some(player.parse().map_err(|_| format!("'{}' isn't a player, 'pilot' or 'none'", player))?)
+            },
+            piloted: args[0] == "pilot",
+            motion: Motion {
+                position: Isometry2::new(Vector2::new(values[0], values[1]), values[2]),
+                centre: Point2::new(values[3], values[4]),
+                lin_vel: Vector2::new(values[5], values[6]),
+                ang_vel: Vector1::new(values[7])
+            },
+            components: Vec::new()
+        })
+    }
+
+    // Give the ship a body in the world
+    pub fn restore(self, ctx: &mut Context) -> Ship {
+        let mut ship = Ship::restore(ctx, self.components, &self.motion);
+        ship.player = self.player;
+
+        if self.piloted {
+            ship.pilot = Some(Box::new(Pilot::new()));
+        }
+
+        ship
+    }
+}
+
+// Make sure an asteroid field can generate asteroids, since out of range values panic or never finish an asteroid
+fn check_asteroids(asteroids: &AsteroidField) -> Result<(), String> {
+    if !asteroids.radius.is_finite() || asteroids.radius <= SPAWN_CLEARANCE {
+        Err(format!("the asteroid radius has to be more than {}, got {}", SPAWN_CLEARANCE, asteroids.radius))
+    } else if asteroids.min_size < 1 || asteroids.min_size > asteroids.max_size {
+        Err(format!("asteroid sizes have to be at least 1 and go from smallest to largest, got {} to {}", asteroids.min_size, asteroids.max_size))
+    } else if asteroids.max_size > i8::max_value() as usize {
+        Err(format!("asteroids can be at most {} squares, got {}", i8::max_value(), asteroids.max_size))
+    } else if asteroids.density.is_nan() || asteroids.density < 0.0 || asteroids.density > 1.0 {
+        Err(format!("the asteroid density has to be from 0 to 1, got {}", asteroids.density))
+    } else if !asteroids.max_drift.is_finite() || asteroids.max_drift <= 0.0 || !asteroids.max_spin.is_finite() || asteroids.max_spin <= 0.0 {
+        Err(format!("asteroids have to drift and spin, got {} and {}", asteroids.max_drift, asteroids.max_spin))
+    } else {
+        Ok(())
+    }
+}
+
+// A game read from a save, which is checked in full before anything is put into the world
+pub struct Save {
+    starfield: u32,
+    ticks: u32,
+    asteroids: AsteroidField,
+    pickups: Pickups,
+    ships: Vec<SavedShip>
+}
+
+impl Save {
+    pub fn parse(text: &str) -> Result<Self, Box<Error>> {
+        let mut save = Self {
+            starfield: 0,
+            ticks: 0,
+            asteroids: AsteroidField::new(0),
+            pickups: Pickups::new(0),
+            ships: Vec::new()
+        };
+
+        let mut version: Option<u32> = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let (directive, args) = (words[0], &words[1..]);
+
+            let result = if directive == "version" {
+                parse_numbers::<u32>(directive, args, 1).map(|values| version = Some(values[0]))
+            } else if version.is_none() {
+                Err("save has to start with its version".to_string())
+            } else {
+                save.parse_line(directive, args)
+            };
+
+            result.map_err(|message| format!("line {}: {}", i + 1, message))?;
+        }
+
+        match version {
+            Some(VERSION) if save.ships.is_empty() => Err("save has no ships".into()),
+            // Every ship needs a part to give it a body
+            Some(VERSION) => match save.ships.iter().position(|ship| ship.components.is_empty()) {
+                Some(i) => Err(format!("ship {} has no parts", i + 1).into()),
+                None => Ok(save)
+            },
+            Some(version) => Err(format!("save is version {}, but only version {} can be loaded", version, VERSION).into()),
+            None => Err("save is empty".into())
+        }
+    }
+
+    fn parse_line(&mut self, directive: &str, args: &[&str]) -> Result<(), String> {
+        match directive {
+            "starfield" => self.starfield = parse_numbers::<u32>(directive, args, 1)?[0],
+            "ticks" => self.ticks = parse_numbers::<u32>(directive, args, 1)?[0],
+            "pickups" => {
+                parse_numbers::<u64>(directive, args, 2)?;
+                let seed = parse_numbers::<u32>(directive, &args[.. 1], 1)?[0];
+                self.pickups = Pickups::resume(seed, parse_numbers::<u64>(directive, &args[1 ..], 1)?[0]);
+            },
+            "asteroids" => {
+                let values = parse_numbers::<f32>(directive, args, 9)?;
+                let seed = parse_numbers::<u32>(directive, &args[.. 1], 1)?[0];
+                let mut asteroids = AsteroidField::resume(seed, parse_numbers::<u64>(directive, &args[1 .. 2], 1)?[0]);
+                asteroids.count = values[2] as usize;
+                asteroids.radius = values[3];
+                asteroids.min_size = values[4] as usize;
+                asteroids.max_size = values[5] as usize;
+                asteroids.density = values[6];
+                asteroids.max_drift = values[7];
+                asteroids.max_spin = values[8];
+                check_asteroids(&asteroids)?;
+                self.asteroids = asteroids;
+            },
+            "ship" => self.ships.push(SavedShip::parse(args)?),
+            "part" => {
+                let component = parse_part(args)?;
+
+                match self.ships.last_mut() {
+                    Some(ship) => ship.components.push(component),
+                    None => return Err("'part' has to come after a 'ship'".into())
+                }
+            },
+            _ => return Err(format!("unknown directive '{}'", directive))
+        }
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<Error>> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Self::parse(&text).map_err(|err| format!("{}: {}", path.display(), err).into())
+    }
+
+    // Put everything from the save into the world
+    pub fn restore(self, ctx: &mut Context) -> (Game, Starfield) {
+        let ships = self.ships.into_iter().map(|saved| saved.restore(ctx)).collect();
+
+        let game = Game::restore(ships, self.asteroids, self.pickups, self.ticks);
+
+        (game, Starfield::new(self.starfield))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use ships::Rotation;
+    use weapons::{Weapon, WeaponType};
+
+    // Save a game to a scratch file and read back what was written
+    fn saved(game: &Game, starfield: &Starfield, name: &str) -> String {
+        let path = env::temp_dir().join(format!("{}.save", name));
+        save(game, starfield, &path).unwrap();
+
+        let mut text = String::new();
+        File::open(&path).unwrap().read_to_string(&mut text).unwrap();
+        fs::remove_file(&path).unwrap();
+        text
+    }
+
+    // Everything but the ship lines, whose angles can come back a rounding error out
+    fn without_ships(text: &str) -> Vec<&str> {
+        text.lines().filter(|line| !line.starts_with("ship")).collect()
+    }
+
+    fn parse_error(text: &str) -> String {
+        Save::parse(text).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut ctx = Context::headless();
+        let components = vec![
+            Component::new(ComponentType::Metal, 0, 0, Rotation::Up),
+            Component::new(ComponentType::hardpoint(WeaponType::Cannon), 0, -1, Rotation::Up)
+        ];
+        let mut ship = Ship::new(&mut ctx, components, 100.0, -50.0, 0.5);
+        ship.player = Some(0);
+
+        let game = Game::new(&mut ctx, vec![ship], 11);
+        let text = saved(&game, &Starfield::new(4), "saves-round-trip");
+
+        let (restored, starfield) = Save::parse(&text).unwrap().restore(&mut Context::headless());
+        let again = saved(&restored, &starfield, "saves-round-trip-again");
+
+        assert_eq!(restored.ships.len(), game.ships.len());
+        assert_eq!(restored.ships[0].player, Some(0));
+        assert_eq!(without_ships(&again), without_ships(&text));
+    }
+
+    #[test]
+    fn weapons_keep_their_charge_and_cooldown() {
+        let mut weapon = Weapon::new(WeaponType::Cannon);
+        weapon.restore(0.5, 0.25, 7, 0.75, true);
+        let component = Component::new(ComponentType::Hardpoint(weapon), 1, 2, Rotation::Left);
+
+        let values = part_values(&component);
+        let parsed = parse_part(&values.split_whitespace().collect::<Vec<_>>()).unwrap();
+
+        assert_eq!(part_values(&parsed), values);
+    }
+
+    #[test]
+    fn resumed_asteroids_carry_on_the_same() {
+        let mut field = AsteroidField::new(7);
+        field.generate();
+
+        let (seed, draws) = field.seed();
+        let mut resumed = AsteroidField::resume(seed, draws);
+
+        let parts = |components: Vec<Component>| components.iter().map(part_values).collect::<Vec<_>>();
+        assert_eq!(parts(resumed.generate()), parts(field.generate()));
+    }
+
+    #[test]
+    fn ships_need_parts() {
+        let text = format!("version {}\nship 0 0 0 0 0 0 0 0 0\n", VERSION);
+        assert_eq!(parse_error(&text), "ship 1 has no parts");
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let text = format!("version {}\n", VERSION - 1);
+        assert!(Save::parse(&text).is_err());
+    }
+
+    #[test]
+    fn impossible_asteroid_fields_are_refused() {
+        let fields = [
+            // Sizes the wrong way round
+            "1 0 12 1500 9 3 0.6 40 1",
+            // Denser than solid
+            "1 0 12 1500 3 14 2 40 1",
+            // Too small to keep clear of the players
+            "1 0 12 500 3 14 0.6 40 1"
+        ];
+
+        for field in &fields {
+            let text = format!("version {}\nasteroids {}\n", VERSION, field);
+            assert!(parse_error(&text).starts_with("line 2:"), "{}", field);
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use colours::{WHITE, GREEN};
 use context::Context;
-use ships::Ship;
+use ships::{Ship, Component};
+
+// How far the mouse can move before a click becomes a drag
+const CLICK_DISTANCE: f32 = 4.0;
 
 pub struct Selection {
     x1: f32,
@@ -24,6 +27,27 @@ impl Selection {
         self.y2 = y2;
     }
 
+    pub fn start(&self) -> (f32, f32) {
+        (self.x1, self.y1)
+    }
+
+    // Whether the mouse has barely moved since the selection started
+    pub fn is_click(&self) -> bool {
+        (self.x2 - self.x1).abs() < CLICK_DISTANCE && (self.y2 - self.y1).abs() < CLICK_DISTANCE
+    }
+
+    // The components of a ship that are inside the selection
+    pub fn components<'a>(&self, ship: &'a Ship) -> Vec<&'a Component> {
+        let rigid_body = ship.handle.borrow();
+
+        ship.components.iter()
+            .filter(|c| {
+                let (pos, _) = c.position(rigid_body.position());
+                self.includes((pos.x, pos.y))
+            })
+            .collect()
+    }
+
     pub fn draw(&self, ctx: &mut Context, ship: &Ship) {
         ctx.set_colour(GREEN);
         for c in &ship.components {
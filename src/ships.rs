@@ -1,17 +1,40 @@
-use nphysics2d::object::{RigidBody, RigidBodyHandle};
+use nphysics2d::object::{RigidBody, RigidBodyHandle, RigidBodyCollisionGroups};
 use ncollide::shape::{Cuboid, Compound, ShapeHandle};
-use nalgebra::{Vector2, Isometry2, Rotation2, distance, Point2};
+use nalgebra::{Vector1, Vector2, Isometry2, Rotation2, Matrix1, distance, Point2};
 use alga::linear::Transformation;
 use ord_subset::OrdSubsetIterExt;
+use sdl2::pixels::Color;
+
+use std::f32::consts::FRAC_PI_2;
 
 use context::Context;
 use resources::Image;
-use colours::RED;
-use weapons::{Weapon, WeaponType, WeaponRay};
-use Controls;
-
-const SIZE: f32 = 32.0;
+use colours::{RED, WHITE, YELLOW};
+use weapons::{Weapon, WeaponType, Mount, Shots, Damage, wrap_angle};
+use pickups::Pickups;
+use power;
+use thrust;
+use assist::FlightAssist;
+use controllers::{Controller, Input};
+
+pub const SIZE: f32 = 32.0;
 const RADIUS: f32 = SIZE / 2.0;
+// The impulse an engine in full working order gives each tick at full throttle
+const ENGINE_THRUST: f32 = 1_000.0;
+// How much of the engines' turning power turning left or right asks for, since all of it spins ships faster than they can be steered
+const TURN_THROTTLE: f32 = 0.25;
+// How far the centre of mass marker reaches either way when debugging
+const COM_MARKER: f32 = 6.0;
+
+// Health fractions below which components show cracks, and below which they work at half strength
+const LIGHT_DAMAGE: f32 = 0.66;
+const HEAVY_DAMAGE: f32 = 0.33;
+const CRITICAL_DAMAGE: f32 = 0.25;
+
+// How many rounds a full magazine holds
+pub const MAGAZINE_CAPACITY: u32 = 40;
+// How much ore a collector can hold
+pub const COLLECTOR_CAPACITY: u32 = 50;
 
 #[derive(Clone)]
 pub enum Rotation {
@@ -34,6 +57,16 @@ impl Rotation {
     fn to_radians(&self) -> f32 {
         self.to_degrees().to_radians()
     }
+
+    // The next rotation clockwise
+    pub fn clockwise(&self) -> Self {
+        match *self {
+            Rotation::Up => Rotation::Right,
+            Rotation::Right => Rotation::Down,
+            Rotation::Down => Rotation::Left,
+            Rotation::Left => Rotation::Up
+        }
+    }
 }
 
 #[derive(is_enum_variant, Clone)]
@@ -43,6 +76,10 @@ pub enum ComponentType {
     Reactor,
     Engine,
     Rock,
+    // A store of ammunition, holding the number of rounds left
+    Magazine(u32),
+    // A hold that takes in ore floating nearby, holding the amount collected
+    Collector(u32),
     Hardpoint(Weapon)
 }
 
@@ -62,11 +99,13 @@ impl ComponentType {
             ComponentType::Engine => Image::Engine,
             ComponentType::Pipe => Image::Pipe,
             ComponentType::Rock => Image::Rock,
+            ComponentType::Magazine(_) => Image::Magazine,
+            ComponentType::Collector(_) => Image::Collector,
             ComponentType::Hardpoint(_) => Image::Hardpoint
         }
     }
 
-    fn density(&self) -> f32 {
+    pub fn density(&self) -> f32 {
         match *self {
             ComponentType::Rock => 1.0,
             ComponentType::Pipe => 0.25,
@@ -74,40 +113,132 @@ impl ComponentType {
         }
     }
 
-    fn max_health(&self) -> i16 {
-        100
+    fn max_health(&self) -> f32 {
+        100.0
+    }
+
+    // Armour as a flat reduction to each hit that penetration can get through,
+    // and a fraction of the rest that's resisted no matter what
+    fn armour(&self) -> (f32, f32) {
+        match *self {
+            ComponentType::Metal => (1.5, 0.0),
+            ComponentType::Reactor | ComponentType::Hardpoint(_) => (1.0, 0.0),
+            ComponentType::Engine | ComponentType::Magazine(_) | ComponentType::Collector(_) => (0.5, 0.0),
+            ComponentType::Pipe => (0.0, 0.0),
+            // Rock is too thick to punch through, but soaks up a share of everything
+            ComponentType::Rock => (0.0, 0.5)
+        }
+    }
+
+    // Whether power can flow through the component to others
+    pub fn conducts(&self) -> bool {
+        match *self {
+            ComponentType::Reactor | ComponentType::Pipe => true,
+            _ => false
+        }
+    }
+
+    pub fn power_output(&self) -> f32 {
+        match *self {
+            ComponentType::Reactor => 4.0,
+            _ => 0.0
+        }
+    }
+
+    pub fn power_demand(&self) -> f32 {
+        match *self {
+            ComponentType::Engine => 1.5,
+            ComponentType::Hardpoint(ref weapon) => weapon.tag().power_demand(),
+            _ => 0.0
+        }
     }
 
     // Create a weapon hardpoint
     pub fn hardpoint(tag: WeaponType) -> Self {
         ComponentType::Hardpoint(Weapon::new(tag))
     }
+
+    // Create a full magazine
+    pub fn magazine() -> Self {
+        ComponentType::Magazine(MAGAZINE_CAPACITY)
+    }
+
+    // Create an empty collector
+    pub fn collector() -> Self {
+        ComponentType::Collector(0)
+    }
 }
 
 #[derive(Clone)]
 pub struct Component {
-    tag: ComponentType,
-    rotation: Rotation,
-    x: i8,
-    y: i8,
-    health: i16
+    pub tag: ComponentType,
+    pub rotation: Rotation,
+    pub x: i8,
+    pub y: i8,
+    // The fraction of its power demand that the component is receiving
+    pub power: f32,
+    health: f32
 }
 
 impl Component {
     pub fn new(tag: ComponentType, x: i8, y: i8, rotation: Rotation) -> Self {
         Self {
             health: tag.max_health(),
+            power: 0.0,
             tag, rotation, x, y,
         }
     }
 
     fn draw_at(&self, ctx: &mut Context, base: &Isometry2<f32>) {
         let (pos, rotation) = self.position(base);
-        ctx.draw_rotated(&self.tag.image(), pos.x, pos.y, SIZE, SIZE, rotation.to_degrees() + self.rotation.to_degrees());
+        let angle = rotation.to_degrees() + self.rotation.to_degrees();
+        let health = self.health_fraction();
+
+        // Fade towards red as the component gets damaged
+        let fade = (health * 255.0) as u8;
+        ctx.draw_tinted(&self.tag.image(), pos.x, pos.y, SIZE, SIZE, angle, Color {r: 255, g: fade, b: fade, a: 255});
+
+        if health < HEAVY_DAMAGE {
+            ctx.draw_rotated(&Image::CracksHeavy, pos.x, pos.y, SIZE, SIZE, angle);
+        } else if health < LIGHT_DAMAGE {
+            ctx.draw_rotated(&Image::CracksLight, pos.x, pos.y, SIZE, SIZE, angle);
+        }
 
         if let ComponentType::Hardpoint(ref weapon) = self.tag {
             weapon.draw(ctx, pos, rotation);
         }
+
+        if ctx.debug {
+            let (x, y) = ctx.camera.world_to_screen(pos.x, pos.y);
+            ctx.set_colour(WHITE);
+            ctx.draw_number(self.health.ceil() as i32, x, y, 8.0);
+        }
+    }
+
+    pub fn health(&self) -> f32 {
+        self.health
+    }
+
+    // Each component is a square of its material, whether or not it collides
+    pub fn mass(&self) -> f32 {
+        self.tag.density() * SIZE * SIZE
+    }
+
+    pub fn set_health(&mut self, health: f32) {
+        self.health = health.max(0.0).min(self.tag.max_health());
+    }
+
+    fn health_fraction(&self) -> f32 {
+        self.health / self.tag.max_health()
+    }
+
+    // How well the component works, which drops when it's badly damaged
+    fn condition(&self) -> f32 {
+        if self.health_fraction() < CRITICAL_DAMAGE {
+            0.5
+        } else {
+            1.0
+        }
     }
 
     // The vector to the center of the ship
@@ -126,151 +257,621 @@ impl Component {
         (base.translation.vector + self.vector_rotated(rotation), rotation)
     }
 
-    // Move the component's weapon if it has one
-    fn step_weapon(&mut self, base: &Isometry2<f32>, controls: &Controls, rays: &mut Vec<WeaponRay>) {
-        let (pos, rotation) = self.position(base);
+    // Move the component's weapon if it has one, returning the recoil impulse and where to apply it relative to the centre of mass
+    fn step_weapon(&mut self, motion: &Motion, input: &Input, ammo: &mut u32, shots: &mut Shots) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        let (pos, rotation) = self.position(&motion.position);
+
+        let mount = Mount {
+            position: pos,
+            rotation,
+            // Rotations are for images, which face up rather than along the x axis
+            facing: self.rotation.to_radians() - FRAC_PI_2,
+            velocity: motion.velocity_at(pos),
+            power: self.power,
+            condition: self.condition()
+        };
+
         if let ComponentType::Hardpoint(ref mut weapon) = self.tag {
-            weapon.step(pos, rotation, controls, rays)
+            weapon.step(&mount, input, ammo, shots).map(|recoil| (recoil, pos - motion.centre.coords))
+        } else {
+            None
         }
     }
 
-    // Damage the component and return if it has been destroyed
-    fn damage(&mut self, damage: i16) -> bool {
-        self.health = self.health.saturating_sub(damage);
-        self.health == 0
+    // Whether the component shares an edge with the grid square at x, y
+    pub fn adjacent(&self, x: i8, y: i8) -> bool {
+        (i16::from(self.x) - i16::from(x)).abs() + (i16::from(self.y) - i16::from(y)).abs() == 1
     }
-}
 
-pub enum ThrustDirection {
-    Forwards,
-    Left,
-    Right
-}
+    // Damage the component and return if it has been destroyed
+    fn damage(&mut self, damage: &Damage) -> bool {
+        let (flat, resistance) = self.tag.armour();
+        let amount = (damage.amount - (flat - damage.penetration).max(0.0)).max(0.0) * (1.0 - resistance);
 
-impl ThrustDirection {
-    fn direction(&self, rotation: f32) -> Vector2<f32> {
-        Rotation2::new(rotation).transform_vector(& match *self {
-            ThrustDirection::Forwards => Vector2::new(0.0,  -1_000.0),
-            ThrustDirection::Left     => Vector2::new(-100.0, 0.0),
-            ThrustDirection::Right    => Vector2::new( 100.0, 0.0)
-        })
+        self.health = (self.health - amount).max(0.0);
+        self.health <= 0.0
     }
 }
 
 pub struct Ship {
     pub components: Vec<Component>,
-    pub handle: RigidBodyHandle<f32>
+    pub handle: RigidBodyHandle<f32>,
+    // A number for telling the ship apart from others across ticks, which the game hands out and is 0 until then
+    pub id: u32,
+    // The player flying the ship, or what flies it when it isn't a player's
+    pub player: Option<usize>,
+    pub pilot: Option<Box<Controller>>,
+    // Where the ship was before the last tick, for drawing in between ticks
+    previous: Isometry2<f32>,
+    // Where each engine is relative to the centre of mass and the impulse it gave in the last tick, in the ship's own frame, for drawing
+    thrusting: Vec<(Vector2<f32>, Vector2<f32>)>,
+    // All the damage the ship's components have taken after armour, for shaking the view of the player flying it
+    damage_taken: f32,
+    // What flight assist has built up, which isn't saved or sent over the network
+    pub assist: FlightAssist
 }
 
 impl Ship {
-    pub fn new(ctx: &mut Context, components: Vec<Component>, x: f32, y: f32, rotation: f32) -> Self {        
+    pub fn new(ctx: &mut Context, mut components: Vec<Component>, x: f32, y: f32, rotation: f32) -> Self {
+        power::distribute(&mut components);
+        let handle = Self::create_rigid_body(ctx, &components, x, y, rotation);
+        Self::with_body(components, handle, Isometry2::new(Vector2::new(x, y), rotation))
+    }
+
+    // A ship from components that are already powered and have a body, drawn from a position until its first tick.
+    // New, restored and broken-off ships are all made here
+    fn with_body(components: Vec<Component>, handle: RigidBodyHandle<f32>, previous: Isometry2<f32>) -> Self {
         Self {
-            handle: Self::create_rigid_body(ctx, &components, x, y, rotation),
-            components
+            handle,
+            components,
+            id: 0,
+            player: None,
+            pilot: None,
+            previous,
+            thrusting: Vec::new(),
+            damage_taken: 0.0,
+            assist: FlightAssist::default()
         }
     }
 
     pub fn mass(&self) -> f32 {
-        self.components.iter().map(|component| component.tag.density()).sum()
+        self.components.iter().map(|component| component.mass()).sum()
     }
 
-    pub fn thrust(&mut self, direction: ThrustDirection, ctx: &mut Context) {
-        if !self.components.iter().any(|component| component.tag.is_reactor()) {
-            return;
-        }
-        
-        let mut rigid_body = self.handle.borrow_mut();
-        
-        let (pos, rotation) = {
-            let position = rigid_body.position();
-            (position.translation.vector, position.rotation.arg())
-        };
+    // Whether the ship is nothing but rock
+    pub fn is_asteroid(&self) -> bool {
+        self.components.iter().all(|component| component.tag.is_rock())
+    }
 
-        ctx.set_colour(RED);
+    // The health left across all components
+    pub fn health(&self) -> f32 {
+        self.components.iter().map(|component| component.health).sum()
+    }
+
+    pub fn damage_taken(&self) -> f32 {
+        self.damage_taken
+    }
 
+    // The position of the ship in world coordinates
+    pub fn position(&self) -> (f32, f32) {
+        let rigid_body = self.handle.borrow();
+        let translation = rigid_body.position().translation.vector;
+        (translation.x, translation.y)
+    }
+
+    // The number of rounds left across all magazines
+    pub fn ammo(&self) -> u32 {
         self.components.iter()
-            .filter(|component| component.tag.is_engine())
-            .for_each(|component| {
-                let relative_vector = component.vector_rotated(rotation);
-                let direction = direction.direction(rotation + component.rotation.to_radians());
-                rigid_body.apply_impulse_wrt_point(direction, relative_vector);
+            .map(|component| match component.tag {
+                ComponentType::Magazine(rounds) => rounds,
+                _ => 0
+            })
+            .sum()
+    }
 
-                let component_pos = pos + relative_vector;
-                ctx.draw_line(component_pos.x, component_pos.y, pos.x, pos.y);
-                ctx.draw_line(component_pos.x + direction.x, component_pos.y + direction.y, component_pos.x, component_pos.y);
-            });
+    // The amount of ore held across all collectors
+    pub fn ore(&self) -> u32 {
+        self.components.iter()
+            .map(|component| match component.tag {
+                ComponentType::Collector(ore) => ore,
+                _ => 0
+            })
+            .sum()
     }
 
-    pub fn step(&mut self, controls: &Controls, ctx: &mut Context, rays: &mut Vec<WeaponRay>) {
-        if controls.up {
-            self.thrust(ThrustDirection::Forwards, ctx);
-        } else if controls.left {
-            self.thrust(ThrustDirection::Left, ctx);
-        } else if controls.right {
-            self.thrust(ThrustDirection::Right, ctx);
+    // Take in as much of some ore at a point as the collectors within reach have room for, and return how much was taken
+    pub fn collect(&mut self, point: Point2<f32>, amount: u32, reach: f32) -> u32 {
+        let position = *self.handle.borrow().position();
+        let mut taken = 0;
+
+        for component in &mut self.components {
+            let (pos, _) = component.position(&position);
+
+            if let ComponentType::Collector(ref mut held) = component.tag {
+                if distance(&Point2::from_coordinates(pos), &point) <= reach {
+                    let space = (amount - taken).min(COLLECTOR_CAPACITY - *held);
+                    *held += space;
+                    taken += space;
+                }
+            }
         }
 
-        let rigid_body = self.handle.borrow();
-        let position = rigid_body.position();
+        taken
+    }
 
-        for mut component in &mut self.components {
-            component.step_weapon(position, controls, rays);
+    // Take rounds out of the magazines, emptying them one at a time
+    fn take_ammo(&mut self, mut rounds: u32) {
+        for component in &mut self.components {
+            if let ComponentType::Magazine(ref mut left) = component.tag {
+                let taken = rounds.min(*left);
+                *left -= taken;
+                rounds -= taken;
+            }
         }
     }
 
-    pub fn draw(&self, ctx: &mut Context) {
-        let rigid_body = self.handle.borrow();
-        let position = rigid_body.position();
+    // The engines that work as the thrust allocator sees them, along with the ship's mass and rotational inertia
+    fn engines(&self) -> (Vec<thrust::Engine>, f32, f32) {
+        let (mass, centre, inertia) = Self::mass_properties(&self.components);
+
+        let engines = self.components.iter()
+            .filter(|component| component.tag.is_engine() && component.power > 0.0)
+            .map(|component| thrust::Engine {
+                position: component.vector() - centre.coords,
+                // Engines push the way they face, and face up like the ship when they aren't rotated
+                direction: Rotation2::new(component.rotation.to_radians()).transform_vector(&Vector2::new(0.0, -1.0)),
+                // Engines that are short on power or badly damaged give less thrust
+                thrust: ENGINE_THRUST * component.power * component.condition()
+            })
+            .collect();
+
+        (engines, mass, inertia)
+    }
 
-        self.components.iter().for_each(|component| component.draw_at(ctx, position));
+    // Fire the engines to get as near as they can to a change in velocity, in the ship's own frame, and a change in spin this tick
+    pub fn accelerate(&mut self, linear: Vector2<f32>, angular: f32) {
+        let (engines, mass, inertia) = self.engines();
+        let impulses = thrust::allocate(&engines, mass, inertia, linear, angular);
 
-        let (x, y) = (position.translation.vector.x, position.translation.vector.y);
-        ctx.set_colour(RED);
-        ctx.draw_point(x as i32, y as i32);
+        let mut rigid_body = self.handle.borrow_mut();
+        let rotation = Rotation2::new(rigid_body.position().rotation.arg());
+
+        for (engine, impulse) in engines.iter().zip(&impulses) {
+            rigid_body.apply_impulse_wrt_point(rotation.transform_vector(impulse), rotation.transform_vector(&engine.position));
+        }
+
+        self.thrusting = engines.into_iter().map(|engine| engine.position).zip(impulses).collect();
     }
 
-    pub fn damage(&mut self, point: Point2<f32>, ctx: &mut Context) -> bool {
-        let (component, x, y, rotation, lin_vel, ang_vel) = {
+    // The most the engines can change the ship's velocity forwards and its spin either way in a tick
+    pub fn thrust_limits(&self) -> (f32, f32) {
+        let (engines, mass, inertia) = self.engines();
+        let forwards = thrust::reach(&engines, mass, inertia, Vector2::new(0.0, -1.0), 0.0);
+        let turning = thrust::reach(&engines, mass, inertia, Vector2::new(0.0, 0.0), 1.0)
+            .min(thrust::reach(&engines, mass, inertia, Vector2::new(0.0, 0.0), -1.0));
+        (forwards, turning)
+    }
+
+    // Remember where the ship is before a tick moves it, and forget how it was thrusting
+    pub fn start_tick(&mut self) {
+        self.previous = *self.handle.borrow().position();
+        self.thrusting.clear();
+    }
+
+    // Where to draw the ship, part way from where it was before the last tick to where it is now
+    pub fn interpolate(&self, alpha: f32) -> Isometry2<f32> {
+        let rigid_body = self.handle.borrow();
+        let current = rigid_body.position();
+        let translation = self.previous.translation.vector + (current.translation.vector - self.previous.translation.vector) * alpha;
+        let rotation = self.previous.rotation.arg() + wrap_angle(current.rotation.arg() - self.previous.rotation.arg()) * alpha;
+        Isometry2::new(translation, rotation)
+    }
+
+    pub fn step(&mut self, input: &Input, shots: &mut Shots) {
+        let throttle = input.throttle.max(0.0).min(1.0);
+        // Turning left swings the nose anticlockwise on screen, which is towards smaller angles
+        let turn = f32::from(input.right as u8) - f32::from(input.left as u8);
+
+        let (forwards, turning) = if input.up || turn != 0.0 { self.thrust_limits() } else { (0.0, 0.0) };
+        let forwards = if input.up { forwards } else { 0.0 };
+
+        let (position, lin_vel, ang_vel) = {
             let rigid_body = self.handle.borrow();
-            let position = rigid_body.position();
-
-            (
-                self.components.iter_mut().enumerate()
-                    .filter(|&(_, ref component)| component.tag.collides())
-                    .ord_subset_min_by_key(|&(_, ref component)| distance(&Point2::from_coordinates(component.position(position).0), &point))
-                    .map(|(i, component)| (i, component.damage(1))),
-                position.translation.vector.x, position.translation.vector.y, position.rotation.arg(),
-                rigid_body.lin_vel(), rigid_body.ang_vel()
-            )
+            (*rigid_body.position(), rigid_body.lin_vel(), rigid_body.ang_vel().x)
         };
 
+        // Flight assist takes over whatever the pilot isn't doing, and keeps track of the ship even while it's off
+        let (linear, angular) = self.assist.step(input, &position, lin_vel, ang_vel,
+            Vector2::new(0.0, -forwards * throttle), turn * turning * TURN_THROTTLE * throttle);
+
+        if linear != Vector2::new(0.0, 0.0) || angular != 0.0 {
+            self.accelerate(linear, angular);
+        }
+
+        let motion = Motion::of(&self.handle);
+        let mut ammo = self.ammo();
+
+        let recoil = self.components.iter_mut()
+            .filter_map(|component| component.step_weapon(&motion, input, &mut ammo, shots))
+            .collect::<Vec<_>>();
+
+        let used = self.ammo() - ammo;
+        self.take_ammo(used);
+
+        // Push the ship back from anything its weapons fired
+        let mut rigid_body = self.handle.borrow_mut();
+
+        for (impulse, relative) in recoil {
+            rigid_body.apply_impulse_wrt_point(impulse, relative);
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, alpha: f32) {
+        let position = self.interpolate(alpha);
+
+        self.components.iter().for_each(|component| component.draw_at(ctx, &position));
+
+        let (pos, rotation) = (position.translation.vector, position.rotation.arg());
+        ctx.set_colour(RED);
+        ctx.draw_point(pos.x as i32, pos.y as i32);
+
+        // Show each engine's pull on the ship
+        if !self.thrusting.is_empty() {
+            let centre = Self::mass_properties(&self.components).1.coords;
+            let turn = Rotation2::new(rotation);
+
+            for &(relative, impulse) in &self.thrusting {
+                let component_pos = pos + turn.transform_vector(&(relative + centre));
+                let impulse = turn.transform_vector(&impulse);
+                ctx.draw_line(component_pos.x, component_pos.y, pos.x, pos.y);
+                ctx.draw_line(component_pos.x + impulse.x, component_pos.y + impulse.y, component_pos.x, component_pos.y);
+            }
+        }
+
+        // Mark the centre of mass, which the ship turns about
+        if ctx.debug {
+            let centre = position * Self::mass_properties(&self.components).1;
+            ctx.set_colour(YELLOW);
+            ctx.draw_line(centre.x - COM_MARKER, centre.y, centre.x + COM_MARKER, centre.y);
+            ctx.draw_line(centre.x, centre.y - COM_MARKER, centre.x, centre.y + COM_MARKER);
+        }
+    }
+
+    // The index of the solid component closest to a point in world coordinates
+    pub fn nearest_component(&self, point: Point2<f32>) -> Option<usize> {
+        let rigid_body = self.handle.borrow();
+        let position = rigid_body.position();
+
+        self.components.iter().enumerate()
+            .filter(|&(_, component)| component.tag.collides())
+            .ord_subset_min_by_key(|&(_, component)| distance(&Point2::from_coordinates(component.position(position).0), &point))
+            .map(|(i, _)| i)
+    }
+
+    // Damage the ship at a point, pushing any pieces that have broken off into debris and dropping ore from destroyed rock,
+    // and return if it has been destroyed
+    pub fn damage(&mut self, point: Point2<f32>, damage: &Damage, ctx: &mut Context, debris: &mut Vec<Ship>, pickups: &mut Pickups) -> bool {
+        let component = self.nearest_component(point).map(|i| {
+            let health = self.components[i].health;
+            let destroyed = self.components[i].damage(damage);
+            self.damage_taken += health - self.components[i].health;
+            (i, destroyed)
+        });
+
         if let Some((index, true)) = component {
-            self.components.remove(index);
+            let motion = Motion::of(&self.handle);
+            let destroyed = self.components.remove(index);
             ctx.remove_rigid_body(&self.handle);
 
-            if self.components.len() == 0 {
-                true
-            } else {
-                self.handle = Self::create_rigid_body(ctx, &self.components, x, y, rotation);
-                let mut body = self.handle.borrow_mut();
-                body.set_lin_vel(lin_vel);
-                body.set_ang_vel(ang_vel);
+            if destroyed.tag.is_rock() {
+                let (pos, _) = destroyed.position(&motion.position);
+                pickups.drop_ore(ctx, pos, motion.velocity_at(pos));
+            }
+
+            // Pieces with nothing solid in them, like a lone engine, can't be hit or run into anything, so they're lost
+            let components = self.components.drain(..).collect();
+            let mut islands = Self::islands(components).into_iter()
+                .filter(|island| island.iter().any(|component| component.tag.collides()));
+
+            if let Some(largest) = islands.next() {
+                // The largest island stays as this ship and the rest fly off as new ones
+                self.components = largest;
+                power::distribute(&mut self.components);
+                self.handle = motion.body(ctx, &self.components);
+
+                let previous = self.previous;
+
+                debris.extend(islands.map(|mut components| {
+                    power::distribute(&mut components);
+                    let handle = motion.body(ctx, &components);
+                    Ship::with_body(components, handle, previous)
+                }));
+
                 false
+            } else {
+                true
             }
         } else {
             false
         }
     }
 
+    // Recreate a ship from saved components, with its body where it was and moving as it was
+    pub fn restore(ctx: &mut Context, mut components: Vec<Component>, motion: &Motion) -> Self {
+        power::distribute(&mut components);
+        let handle = motion.body(ctx, &components);
+        Self::with_body(components, handle, motion.position)
+    }
+
+    pub fn motion(&self) -> Motion {
+        Motion::of(&self.handle)
+    }
+
+    // Put the ship somewhere else and set it moving, without rebuilding its body
+    pub fn set_motion(&mut self, motion: &Motion) {
+        let mut body = self.handle.borrow_mut();
+        body.set_transformation(motion.position);
+        let lin_vel = motion.velocity_at(body.center_of_mass().coords);
+        body.set_lin_vel(lin_vel);
+        body.set_ang_vel(motion.ang_vel);
+    }
+
+    // Recreate the rigid body and power network after the components have changed, keeping it where it was and moving as it was
+    pub fn rebuild(&mut self, ctx: &mut Context) {
+        power::distribute(&mut self.components);
+
+        let motion = Motion::of(&self.handle);
+        ctx.remove_rigid_body(&self.handle);
+        self.handle = motion.body(ctx, &self.components);
+    }
+
+    // The grid square on the ship that a point in world coordinates falls in
+    pub fn grid_position(&self, (x, y): (f32, f32)) -> (i8, i8) {
+        let local = self.handle.borrow().position().inverse() * Point2::new(x, y);
+        ((local.x / SIZE).round() as i8, (local.y / SIZE).round() as i8)
+    }
+
+    // Split components into groups that are connected along the grid, largest first
+    fn islands(mut components: Vec<Component>) -> Vec<Vec<Component>> {
+        let mut islands = Vec::new();
+
+        while let Some(first) = components.pop() {
+            let mut island = vec![first];
+            let mut i = 0;
+
+            // Flood fill outwards, moving each neighbour into the island as it is found
+            while i < island.len() {
+                let (x, y) = (island[i].x, island[i].y);
+                let mut j = 0;
+
+                while j < components.len() {
+                    if components[j].adjacent(x, y) {
+                        island.push(components.swap_remove(j));
+                    } else {
+                        j += 1;
+                    }
+                }
+
+                i += 1;
+            }
+
+            islands.push(island);
+        }
+
+        islands.sort_by(|a, b| b.len().cmp(&a.len()));
+        islands
+    }
+
+    // The mass, centre of mass relative to the ship's origin, and rotational inertia about that centre of a set of components.
+    // Parts that don't collide, like engines and pipes, still weigh what they weigh
+    fn mass_properties(components: &Vec<Component>) -> (f32, Point2<f32>, f32) {
+        let mass = components.iter().map(|component| component.mass()).sum::<f32>();
+
+        let centre = if mass > 0.0 {
+            components.iter().fold(Vector2::new(0.0, 0.0), |centre, component| centre + component.vector() * component.mass()) / mass
+        } else {
+            Vector2::new(0.0, 0.0)
+        };
+
+        // Each square spins about its own middle, plus the parallel axis term for being away from the centre of mass
+        let inertia = components.iter()
+            .map(|component| component.mass() * (SIZE * SIZE / 6.0 + (component.vector() - centre).norm_squared()))
+            .sum::<f32>();
+
+        (mass, Point2::from_coordinates(centre), inertia)
+    }
+
     fn create_rigid_body(ctx: &mut Context, components: &Vec<Component>, x: f32, y: f32, rotation: f32) -> RigidBodyHandle<f32> {
         let shape = ShapeHandle::new(Cuboid::new(Vector2::new(RADIUS, RADIUS)));
-        let density = components.iter().map(|component| component.tag.density()).sum::<f32>() / components.len() as f32;
+        let (mass, centre, inertia) = Self::mass_properties(components);
 
-        ctx.add_rigid_body(RigidBody::new_dynamic(
-            Compound::new(components.iter()
-                .filter(|component| component.tag.collides())
+        // A compound needs at least one shape, so a ship with nothing solid in it gets a square for every part
+        // and is kept from touching anything instead
+        let solid = components.iter().any(|component| component.tag.collides());
+
+        let mut body = RigidBody::new(
+            ShapeHandle::new(Compound::new(components.iter()
+                .filter(|component| !solid || component.tag.collides())
                 .map(|component| (Isometry2::new(component.vector(), 0.0), shape.clone())
-            ).collect()), density, 1.0, 1.0), x, y, rotation
-        )
+            ).collect())), Some((mass, centre, Matrix1::new(inertia))), 1.0, 1.0
+        );
+
+        if !solid {
+            let mut groups = RigidBodyCollisionGroups::new_dynamic();
+            groups.set_whitelist(&[]);
+            body.set_collision_groups(groups);
+        }
+
+        ctx.add_rigid_body(body, x, y, rotation)
+    }
+}
+
+// The state of a rigid body, for carrying over when it is rebuilt, split, saved or sent over the network
+#[derive(Clone)]
+pub struct Motion {
+    pub position: Isometry2<f32>,
+    pub centre: Point2<f32>,
+    pub lin_vel: Vector2<f32>,
+    pub ang_vel: Vector1<f32>
+}
+
+impl Motion {
+    fn of(handle: &RigidBodyHandle<f32>) -> Self {
+        let rigid_body = handle.borrow();
+
+        Self {
+            position: *rigid_body.position(),
+            centre: *rigid_body.center_of_mass(),
+            lin_vel: rigid_body.lin_vel(),
+            ang_vel: rigid_body.ang_vel()
+        }
+    }
+
+    // Create a body for components in the same place, with the velocity its centre of mass had as part of the old body
+    fn body(&self, ctx: &mut Context, components: &Vec<Component>) -> RigidBodyHandle<f32> {
+        let (x, y) = (self.position.translation.vector.x, self.position.translation.vector.y);
+        let handle = Ship::create_rigid_body(ctx, components, x, y, self.position.rotation.arg());
+
+        {
+            let mut body = handle.borrow_mut();
+            let lin_vel = self.velocity_at(body.center_of_mass().coords);
+            body.set_lin_vel(lin_vel);
+            body.set_ang_vel(self.ang_vel);
+        }
+
+        handle
+    }
+
+    // The velocity of a point in world coordinates moving with the body
+    pub fn velocity_at(&self, point: Vector2<f32>) -> Vector2<f32> {
+        let offset = point - self.centre.coords;
+        self.lin_vel + Vector2::new(-offset.y, offset.x) * self.ang_vel.x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metal(x: i8, y: i8) -> Component {
+        Component::new(ComponentType::Metal, x, y, Rotation::Up)
+    }
+
+    fn positions(island: &[Component]) -> Vec<(i8, i8)> {
+        let mut positions = island.iter().map(|component| (component.x, component.y)).collect::<Vec<_>>();
+        positions.sort();
+        positions
+    }
+
+    #[test]
+    fn islands_split_where_the_grid_does() {
+        let islands = Ship::islands(vec![metal(0, 0), metal(3, 0), metal(1, 0), metal(1, 1), metal(3, 1)]);
+
+        assert_eq!(islands.len(), 2);
+        assert_eq!(positions(&islands[0]), vec![(0, 0), (1, 0), (1, 1)]);
+        assert_eq!(positions(&islands[1]), vec![(3, 0), (3, 1)]);
+    }
+
+    #[test]
+    fn islands_dont_join_at_corners() {
+        let islands = Ship::islands(vec![metal(0, 0), metal(1, 1), metal(2, 2)]);
+
+        assert_eq!(islands.len(), 3);
+        assert!(islands.iter().all(|island| island.len() == 1));
+    }
+
+    #[test]
+    fn islands_of_nothing() {
+        assert!(Ship::islands(Vec::new()).is_empty());
+    }
+
+    // The health a fresh component loses to a hit
+    fn health_lost(tag: ComponentType, amount: f32, penetration: f32) -> f32 {
+        let mut component = Component::new(tag, 0, 0, Rotation::Up);
+        component.damage(&Damage { amount, penetration });
+        component.tag.max_health() - component.health()
+    }
+
+    #[test]
+    fn armour_takes_a_flat_amount_off_each_hit() {
+        assert!((health_lost(ComponentType::Metal, 10.0, 0.0) - 8.5).abs() < 1e-6);
+        assert!((health_lost(ComponentType::Pipe, 10.0, 0.0) - 10.0).abs() < 1e-6);
+        assert_eq!(health_lost(ComponentType::Metal, 1.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn penetration_gets_through_armour_but_no_further() {
+        assert!((health_lost(ComponentType::Metal, 10.0, 1.0) - 9.5).abs() < 1e-6);
+        assert!((health_lost(ComponentType::Metal, 10.0, 5.0) - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rock_resists_a_share_of_everything() {
+        assert!((health_lost(ComponentType::Rock, 10.0, 0.0) - 5.0).abs() < 1e-6);
+        assert!((health_lost(ComponentType::Rock, 10.0, 5.0) - 5.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn components_are_destroyed_at_no_health() {
+        let mut component = metal(0, 0);
+
+        assert!(!component.damage(&Damage { amount: 50.0, penetration: 2.0 }));
+        assert!(component.damage(&Damage { amount: 60.0, penetration: 2.0 }));
+        assert_eq!(component.health(), 0.0);
+    }
+
+    #[test]
+    fn weapons_fire_from_the_turning_ship_and_recoil_about_its_centre_of_mass() {
+        let mut component = Component::new(ComponentType::hardpoint(WeaponType::Cannon), 1, 0, Rotation::Right);
+        component.power = 1.0;
+
+        // A ship at the origin spinning about a centre of mass half a square to the right
+        let motion = Motion {
+            position: Isometry2::new(Vector2::new(0.0, 0.0), 0.0),
+            centre: Point2::new(SIZE / 2.0, 0.0),
+            lin_vel: Vector2::new(0.0, 0.0),
+            ang_vel: Vector1::new(0.5)
+        };
+
+        let mut input = Input::default();
+        input.fire = [true; ::weapons::GROUPS];
+        input.aim_direction = Some((1.0, 0.0));
+
+        let (mut ammo, mut shots) = (10, Shots::default());
+        let (_, relative) = component.step_weapon(&motion, &input, &mut ammo, &mut shots).unwrap();
+        let speed = WeaponType::Cannon.ballistics().unwrap().speed;
+
+        assert!((relative - Vector2::new(SIZE / 2.0, 0.0)).norm() < 1e-3);
+        assert!((shots.projectiles[0].velocity() - Vector2::new(speed, SIZE / 2.0 * 0.5)).norm() < 1e-3);
+    }
+
+    #[test]
+    fn centre_of_mass_between_equal_parts() {
+        let (mass, centre, inertia) = Ship::mass_properties(&vec![metal(0, 0), metal(1, 0)]);
+        let part = metal(0, 0).mass();
+
+        assert!((mass - 2.0 * part).abs() < 1e-3);
+        assert!((centre - Point2::new(SIZE / 2.0, 0.0)).norm() < 1e-3);
+        // Each square's own spin plus how far it is from the middle between them
+        assert!((inertia - 2.0 * part * (SIZE * SIZE / 6.0 + (SIZE / 2.0) * (SIZE / 2.0))).abs() < 1.0);
+    }
+
+    #[test]
+    fn centre_of_mass_leans_to_heavier_parts() {
+        let rock = Component::new(ComponentType::Rock, 0, 0, Rotation::Up);
+        let (mass, centre, _) = Ship::mass_properties(&vec![rock.clone(), metal(1, 0)]);
+
+        assert!((mass - rock.mass() - metal(1, 0).mass()).abs() < 1e-3);
+        assert!((centre.x - metal(1, 0).mass() * SIZE / mass).abs() < 1e-3);
+        assert!(centre.x < SIZE / 2.0);
+    }
+
+    #[test]
+    fn parts_that_dont_collide_still_weigh() {
+        let engine = Component::new(ComponentType::Engine, 0, 1, Rotation::Up);
+        let (mass, centre, _) = Ship::mass_properties(&vec![metal(0, 0), engine]);
+
+        assert!((mass - 2.0 * metal(0, 0).mass()).abs() < 1e-3);
+        assert!((centre - Point2::new(0.0, SIZE / 2.0)).norm() < 1e-3);
     }
 }
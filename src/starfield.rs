@@ -0,0 +1,87 @@
+use rand::{Rng, SeedableRng, XorShiftRng};
+use sdl2::pixels::Color;
+
+use context::Context;
+
+// The size of the square chunks that stars are generated in
+const CHUNK_SIZE: f32 = 512.0;
+
+// A layer of stars at some distance
+struct Layer {
+    // How far the layer moves compared to the world, where nearer layers move more
+    parallax: f32,
+    stars_per_chunk: u32,
+    colour: Color
+}
+
+const LAYERS: [Layer; 3] = [
+    Layer {parallax: 0.1, stars_per_chunk: 24, colour: Color {r:  90, g:  90, b: 110, a: 255}},
+    Layer {parallax: 0.3, stars_per_chunk: 16, colour: Color {r: 160, g: 160, b: 180, a: 255}},
+    Layer {parallax: 0.6, stars_per_chunk:  8, colour: Color {r: 255, g: 255, b: 255, a: 255}}
+];
+
+// Scramble the bits of a number, so that neighbouring chunks get unrelated seeds
+fn mix(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+// An endless field of stars generated from a seed, so the same area always has the same stars
+pub struct Starfield {
+    seed: u32
+}
+
+impl Starfield {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    // The stars in a chunk of a layer, in that layer's coordinates
+    fn chunk(&self, layer: usize, x: i32, y: i32) -> Vec<(f32, f32)> {
+        let hash = mix(self.seed ^ mix(x as u32 ^ mix(y as u32 ^ mix(layer as u32))));
+        // Xorshift can't be seeded with all zeroes
+        let mut rng = XorShiftRng::from_seed([hash, mix(hash), mix(hash ^ 1), mix(hash ^ 2) | 1]);
+        let (left, top) = (x as f32 * CHUNK_SIZE, y as f32 * CHUNK_SIZE);
+
+        (0 .. LAYERS[layer].stars_per_chunk)
+            .map(|_| (left + rng.gen::<f32>() * CHUNK_SIZE, top + rng.gen::<f32>() * CHUNK_SIZE))
+            .collect()
+    }
+
+    pub fn draw(&self, ctx: &mut Context) {
+        let (camera_x, camera_y) = ctx.camera.position();
+        let (width, height) = ctx.camera.size();
+
+        for (i, layer) in LAYERS.iter().enumerate() {
+            // Distant layers scroll and zoom less than the world does
+            let zoom = 1.0 + (ctx.camera.zoom() - 1.0) * layer.parallax;
+            let (centre_x, centre_y) = (camera_x * layer.parallax, camera_y * layer.parallax);
+            let (half_width, half_height) = (width / 2.0 / zoom, height / 2.0 / zoom);
+
+            let (min_x, max_x) = (((centre_x - half_width) / CHUNK_SIZE).floor() as i32, ((centre_x + half_width) / CHUNK_SIZE).floor() as i32);
+            let (min_y, max_y) = (((centre_y - half_height) / CHUNK_SIZE).floor() as i32, ((centre_y + half_height) / CHUNK_SIZE).floor() as i32);
+
+            ctx.set_colour(layer.colour);
+
+            for x in min_x .. max_x + 1 {
+                for y in min_y .. max_y + 1 {
+                    for (star_x, star_y) in self.chunk(i, x, y) {
+                        let screen_x = (star_x - centre_x) * zoom + width / 2.0;
+                        let screen_y = (star_y - centre_y) * zoom + height / 2.0;
+                        ctx.draw_point_screen(screen_x.round() as i32, screen_y.round() as i32);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use nalgebra::{Vector2, Rotation2};
+use alga::linear::Transformation;
+
+// How far either way an engine can swing its nozzle, which lets engines in a line still push the ship round
+const GIMBAL: f32 = 0.3;
+// How many rounds of refinement the allocator gets each time it's asked
+const ITERATIONS: usize = 100;
+// How much more it matters not to turn when nobody asked to than to get all of what was asked for,
+// so lopsided ships fly straight rather than flat out
+const HOLD_WEIGHT: f32 = 4.0;
+
+// An engine as the allocator sees it, in the ship's own frame
+pub struct Engine {
+    // Where the engine is relative to the ship's centre of mass
+    pub position: Vector2<f32>,
+    // Which way the engine pushes the ship with its nozzle straight, of unit length
+    pub direction: Vector2<f32>,
+    // The impulse the engine gives each tick at full throttle
+    pub thrust: f32
+}
+
+// What an engine does to the ship each tick flat out with its nozzle swung by an angle: the change in velocity, and the change in spin
+// times the ship's radius of gyration, so turning and moving can be weighed against each other
+fn effect(engine: &Engine, angle: f32, mass: f32, gyration: f32) -> [f32; 3] {
+    let force = Rotation2::new(angle).transform_vector(&engine.direction) * engine.thrust;
+    let torque = engine.position.x * force.y - engine.position.y * force.x;
+    [force.x / mass, force.y / mass, torque / (mass * gyration)]
+}
+
+// Each engine's nozzle swung fully one way and then the other, with the engine's throttle shared between them
+fn nozzles(engines: &[Engine], mass: f32, gyration: f32) -> Vec<[f32; 3]> {
+    let mut nozzles = Vec::with_capacity(engines.len() * 2);
+
+    for engine in engines {
+        nozzles.push(effect(engine, GIMBAL, mass, gyration));
+        nozzles.push(effect(engine, -GIMBAL, mass, gyration));
+    }
+
+    nozzles
+}
+
+fn dot(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// Keep an engine's two nozzle throttles between nothing and flat out, and together no more than flat out
+fn limit(a: &mut f32, b: &mut f32) {
+    *a = a.max(0.0).min(1.0);
+    *b = b.max(0.0).min(1.0);
+
+    let excess = (*a + *b - 1.0) / 2.0;
+
+    if excess > 0.0 {
+        *a -= excess;
+        *b -= excess;
+
+        if *a < 0.0 {
+            *a = 0.0;
+            *b = 1.0;
+        } else if *b < 0.0 {
+            *a = 1.0;
+            *b = 0.0;
+        }
+    }
+}
+
+// How sharply the miss can change with the throttles at worst, which is the largest eigenvalue of the nozzles' effects
+// multiplied by their own transpose, found by repeatedly multiplying. Steps scaled down by it can't overshoot
+fn steepness(nozzles: &[[f32; 3]]) -> f32 {
+    let mut product = [[0.0; 3]; 3];
+
+    for nozzle in nozzles {
+        for row in 0 .. 3 {
+            for column in 0 .. 3 {
+                product[row][column] += nozzle[row] * nozzle[column];
+            }
+        }
+    }
+
+    let mut vector = [1.0, 1.0, 1.0];
+
+    for _ in 0 .. 20 {
+        let next = [dot(&product[0], &vector), dot(&product[1], &vector), dot(&product[2], &vector)];
+        let length = dot(&next, &next).sqrt();
+
+        if length <= 0.0 {
+            return 0.0;
+        }
+
+        vector = [next[0] / length, next[1] / length, next[2] / length];
+    }
+
+    dot(&vector, &[dot(&product[0], &vector), dot(&product[1], &vector), dot(&product[2], &vector)])
+}
+
+// At most how many times over a change in velocity and spin per tick the engines could give, if every engine that helps fired flat out
+pub fn reach(engines: &[Engine], mass: f32, inertia: f32, linear: Vector2<f32>, angular: f32) -> f32 {
+    if mass <= 0.0 || inertia <= 0.0 {
+        return 0.0;
+    }
+
+    let gyration = (inertia / mass).sqrt();
+    let demand = [linear.x, linear.y, angular * gyration];
+    let size = dot(&demand, &demand);
+
+    if size <= 0.0 {
+        return 0.0;
+    }
+
+    nozzles(engines, mass, gyration).chunks(2)
+        .map(|pair| dot(&pair[0], &demand).max(dot(&pair[1], &demand)).max(0.0))
+        .sum::<f32>() / size
+}
+
+// Work out how hard to fire each engine and which way to swing its nozzle to get as near as possible to a change in velocity,
+// in the ship's own frame, and a change in spin this tick. Returns the impulse each engine gives in the ship's own frame.
+// This is a least squares fit of the throttles, found by stepping down the slope and pulling them back within their limits each time
+pub fn allocate(engines: &[Engine], mass: f32, inertia: f32, linear: Vector2<f32>, angular: f32) -> Vec<Vector2<f32>> {
+    if mass <= 0.0 || inertia <= 0.0 {
+        return engines.iter().map(|_| Vector2::new(0.0, 0.0)).collect();
+    }
+
+    let gyration = (inertia / mass).sqrt();
+    let weights = [1.0, 1.0, if angular == 0.0 { HOLD_WEIGHT } else { 1.0 }];
+    let target = [linear.x, linear.y, angular * gyration * weights[2]];
+
+    let nozzles = nozzles(engines, mass, gyration).into_iter()
+        .map(|nozzle| [nozzle[0] * weights[0], nozzle[1] * weights[1], nozzle[2] * weights[2]])
+        .collect::<Vec<_>>();
+
+    let steepness = steepness(&nozzles);
+    let mut throttles = vec![0.0; nozzles.len()];
+
+    if steepness > 0.0 {
+        for _ in 0 .. ITERATIONS {
+            let mut miss = [-target[0], -target[1], -target[2]];
+
+            for (nozzle, &throttle) in nozzles.iter().zip(&throttles) {
+                miss = [miss[0] + nozzle[0] * throttle, miss[1] + nozzle[1] * throttle, miss[2] + nozzle[2] * throttle];
+            }
+
+            for (nozzle, throttle) in nozzles.iter().zip(throttles.iter_mut()) {
+                *throttle -= dot(nozzle, &miss) / steepness;
+            }
+
+            for pair in throttles.chunks_mut(2) {
+                let (a, b) = pair.split_at_mut(1);
+                limit(&mut a[0], &mut b[0]);
+            }
+        }
+    }
+
+    engines.iter().zip(throttles.chunks(2))
+        .map(|(engine, pair)| {
+            let swung = |angle: f32| Rotation2::new(angle).transform_vector(&engine.direction);
+            (swung(GIMBAL) * pair[0] + swung(-GIMBAL) * pair[1]) * engine.thrust
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASS: f32 = 1000.0;
+    const INERTIA: f32 = 1_000_000.0;
+
+    // An engine pushing the ship forwards, which each tick is enough to change its velocity by 1 flat out
+    fn engine(x: f32, y: f32) -> Engine {
+        Engine {
+            position: Vector2::new(x, y),
+            direction: Vector2::new(0.0, -1.0),
+            thrust: MASS
+        }
+    }
+
+    // The spin the engines' impulses give the ship about its centre of mass
+    fn torque(engines: &[Engine], impulses: &[Vector2<f32>]) -> f32 {
+        engines.iter().zip(impulses)
+            .map(|(engine, impulse)| engine.position.x * impulse.y - engine.position.y * impulse.x)
+            .sum()
+    }
+
+    #[test]
+    fn gets_what_was_asked_for_when_it_can() {
+        let engines = [engine(-30.0, 0.0), engine(30.0, 0.0)];
+        let impulses = allocate(&engines, MASS, INERTIA, Vector2::new(0.0, -1.0), 0.0);
+        let total = impulses.iter().fold(Vector2::new(0.0, 0.0), |total, impulse| total + impulse);
+
+        assert!((total / MASS - Vector2::new(0.0, -1.0)).norm() < 0.01);
+        assert!(torque(&engines, &impulses).abs() < 1.0);
+    }
+
+    #[test]
+    fn never_goes_past_flat_out() {
+        let engines = [engine(0.0, 0.0)];
+        let impulses = allocate(&engines, MASS, INERTIA, Vector2::new(0.0, -10.0), 0.0);
+
+        assert!(impulses[0].norm() <= engines[0].thrust + 0.01);
+        // Swinging the nozzle both ways at once to go straight loses a little
+        assert!(impulses[0].norm() > engines[0].thrust * GIMBAL.cos() - 0.01);
+    }
+
+    #[test]
+    fn turns_with_the_engine_on_the_right_side() {
+        let engines = [engine(-30.0, 0.0), engine(30.0, 0.0)];
+
+        let impulses = allocate(&engines, MASS, INERTIA, Vector2::new(0.0, 0.0), 0.01);
+        assert!(impulses[0].norm() > impulses[1].norm());
+        assert!(torque(&engines, &impulses) > 0.0);
+
+        let impulses = allocate(&engines, MASS, INERTIA, Vector2::new(0.0, 0.0), -0.01);
+        assert!(impulses[1].norm() > impulses[0].norm());
+        assert!(torque(&engines, &impulses) < 0.0);
+    }
+
+    #[test]
+    fn nothing_without_mass() {
+        let engines = [engine(0.0, 0.0)];
+
+        assert!(allocate(&engines, 0.0, INERTIA, Vector2::new(0.0, -1.0), 0.0).iter().all(|impulse| impulse.norm() == 0.0));
+        assert_eq!(reach(&engines, 0.0, INERTIA, Vector2::new(0.0, -1.0), 0.0), 0.0);
+    }
+}
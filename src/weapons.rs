@@ -5,38 +5,343 @@ use sdl2::pixels::Color;
 use ord_subset::OrdSubsetIterExt;
 use alga::general::Inverse;
 
-use Controls;
+use std::f32::consts::PI;
+
+use controllers::Input;
 use context::Context;
 use resources::Image;
-use colours::{RED, GREEN};
+use colours::{RED, GREEN, YELLOW, ORANGE, BLUE};
 use ships::Ship;
+use projectiles::Projectile;
+use pickups::Pickups;
+
+// How far a weapon can turn in a tick, in radians
+const TURN_RATE: f32 = 0.1;
+// How far an overheated weapon has to cool down before it can fire again
+const OVERHEAT_RESET: f32 = 0.3;
+// How far a turret can turn either side of the way its hardpoint faces, so it can't aim through its own hull
+const ARC: f32 = 100.0 * PI / 180.0;
+
+// How much the traction beam speeds up ore towards it each tick
+const ORE_PULL: f32 = 5.0;
+
+// The number of weapon groups that can be fired separately
+pub const GROUPS: usize = 4;
+
+// Wrap an angle into the range -PI to PI
+pub fn wrap_angle(angle: f32) -> f32 {
+    let angle = angle % (2.0 * PI);
+
+    if angle > PI {
+        angle - 2.0 * PI
+    } else if angle < -PI {
+        angle + 2.0 * PI
+    } else {
+        angle
+    }
+}
+
+// A single hit's worth of damage
+pub struct Damage {
+    pub amount: f32,
+    // How much flat armour the hit ignores
+    pub penetration: f32
+}
+
+// How much damage a weapon does and how it drops off with distance
+pub struct DamageProfile {
+    // Damage per tick at close range
+    damage: f32,
+    // The distance at which damage starts to drop off and the distance at which it's gone, or None if it never drops off
+    falloff: Option<(f32, f32)>,
+    penetration: f32
+}
+
+impl DamageProfile {
+    // The damage done to something a distance away from the weapon
+    pub fn at(&self, distance: f32) -> Damage {
+        let falloff = match self.falloff {
+            Some((start, end)) => ((distance - start) / (end - start)).max(0.0).min(1.0),
+            None => 0.0
+        };
+
+        Damage {
+            amount: self.damage * (1.0 - falloff),
+            penetration: self.penetration
+        }
+    }
+
+    // The damage done at close range
+    pub fn damage(&self) -> f32 {
+        self.damage
+    }
+}
+
+// How the shots of a projectile weapon fly
+pub struct Ballistics {
+    // Speed relative to the hardpoint when fired
+    pub speed: f32,
+    // Acceleration along the direction of travel once fired
+    pub thrust: f32,
+    // Mass of each shot, which decides how hard the ship recoils
+    pub mass: f32,
+    // Ticks before a shot burns out
+    pub lifetime: u32
+}
+
+// How fast a weapon can fire and how hot it gets doing it
+pub struct FiringModel {
+    // Ticks between shots
+    pub interval: u32,
+    // Heat added by each shot and lost each tick, where a weapon overheats at 1
+    pub heat_per_shot: f32,
+    pub cooling: f32,
+    // Whether each shot takes a round from the ship's magazines
+    pub uses_ammo: bool
+}
 
 #[derive(Copy, Clone)]
 pub enum WeaponType {
     Laser,
-    TractionBeam
+    TractionBeam,
+    Cannon,
+    Missile,
+    MassDriver
 }
 
 impl WeaponType {
+    // A word for the weapon, for sending shots over the network
+    pub fn name(&self) -> &'static str {
+        match *self {
+            WeaponType::Laser => "laser",
+            WeaponType::TractionBeam => "traction",
+            WeaponType::Cannon => "cannon",
+            WeaponType::Missile => "missile",
+            WeaponType::MassDriver => "massdriver"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "laser" => Some(WeaponType::Laser),
+            "traction" => Some(WeaponType::TractionBeam),
+            "cannon" => Some(WeaponType::Cannon),
+            "missile" => Some(WeaponType::Missile),
+            "massdriver" => Some(WeaponType::MassDriver),
+            _ => None
+        }
+    }
+
     fn image(&self) -> Image {
         match *self {
             WeaponType::Laser => Image::Laser,
-            WeaponType::TractionBeam => Image::TractionBeam
+            WeaponType::TractionBeam => Image::TractionBeam,
+            WeaponType::Cannon => Image::Cannon,
+            WeaponType::Missile => Image::MissileLauncher,
+            WeaponType::MassDriver => Image::MassDriver
+        }
+    }
+
+    // How shots fly, or None for weapons that fire rays
+    pub fn ballistics(&self) -> Option<Ballistics> {
+        match *self {
+            WeaponType::Laser | WeaponType::TractionBeam => None,
+            WeaponType::Cannon => Some(Ballistics {
+                speed: 600.0,
+                thrust: 0.0,
+                mass: 50.0,
+                lifetime: 180
+            }),
+            WeaponType::Missile => Some(Ballistics {
+                speed: 150.0,
+                thrust: 900.0,
+                mass: 80.0,
+                lifetime: 240
+            }),
+            WeaponType::MassDriver => Some(Ballistics {
+                speed: 1500.0,
+                thrust: 0.0,
+                mass: 100.0,
+                lifetime: 120
+            })
+        }
+    }
+
+    // The group a weapon fires with, so beams and guns can be used separately
+    fn default_group(&self) -> usize {
+        match *self {
+            WeaponType::Laser => 0,
+            WeaponType::TractionBeam => 1,
+            WeaponType::Cannon | WeaponType::MassDriver => 2,
+            WeaponType::Missile => 3
         }
     }
 
-    fn ray_colour(&self) -> Color {
+    pub fn firing_model(&self) -> FiringModel {
+        match *self {
+            WeaponType::Laser => FiringModel {
+                interval: 1,
+                heat_per_shot: 0.008,
+                cooling: 0.004,
+                uses_ammo: false
+            },
+            WeaponType::TractionBeam => FiringModel {
+                interval: 1,
+                heat_per_shot: 0.003,
+                cooling: 0.004,
+                uses_ammo: false
+            },
+            WeaponType::Cannon => FiringModel {
+                interval: 15,
+                heat_per_shot: 0.08,
+                cooling: 0.003,
+                uses_ammo: true
+            },
+            WeaponType::Missile => FiringModel {
+                interval: 45,
+                heat_per_shot: 0.15,
+                cooling: 0.004,
+                uses_ammo: true
+            },
+            WeaponType::MassDriver => FiringModel {
+                interval: 60,
+                heat_per_shot: 0.35,
+                cooling: 0.003,
+                uses_ammo: true
+            }
+        }
+    }
+
+    pub fn damage_profile(&self) -> DamageProfile {
+        match *self {
+            WeaponType::Laser => DamageProfile {
+                damage: 2.0,
+                falloff: Some((400.0, 1500.0)),
+                penetration: 1.0
+            },
+            WeaponType::TractionBeam => DamageProfile {
+                damage: 0.0,
+                falloff: None,
+                penetration: 0.0
+            },
+            WeaponType::Cannon => DamageProfile {
+                damage: 12.0,
+                falloff: Some((1500.0, 4000.0)),
+                penetration: 1.0
+            },
+            // Missiles keep accelerating, so they hit just as hard at any range
+            WeaponType::Missile => DamageProfile {
+                damage: 25.0,
+                falloff: None,
+                penetration: 0.5
+            },
+            WeaponType::MassDriver => DamageProfile {
+                damage: 20.0,
+                falloff: Some((2000.0, 6000.0)),
+                penetration: 3.0
+            }
+        }
+    }
+
+    pub fn power_demand(&self) -> f32 {
+        match *self {
+            WeaponType::Laser => 2.0,
+            WeaponType::TractionBeam | WeaponType::Missile => 1.0,
+            WeaponType::Cannon => 1.5,
+            WeaponType::MassDriver => 3.0
+        }
+    }
+
+    // The colour of rays and projectile tracers
+    pub fn colour(&self) -> Color {
         match *self {
             WeaponType::Laser => RED,
-            WeaponType::TractionBeam => GREEN
+            WeaponType::TractionBeam => GREEN,
+            WeaponType::Cannon => YELLOW,
+            WeaponType::Missile => ORANGE,
+            WeaponType::MassDriver => BLUE
+        }
+    }
+}
+
+// Where a weapon is mounted and how well it's supplied, for a single tick
+pub struct Mount {
+    pub position: Vector2<f32>,
+    pub rotation: f32,
+    // The direction the hardpoint faces, relative to the ship
+    pub facing: f32,
+    // The velocity of the hardpoint, which shots inherit
+    pub velocity: Vector2<f32>,
+    pub power: f32,
+    pub condition: f32
+}
+
+// Everything that weapons have fired
+#[derive(Default)]
+pub struct Shots {
+    // Rays only last for the tick they were fired in
+    pub rays: Vec<WeaponRay>,
+    pub projectiles: Vec<Projectile>
+}
+
+impl Shots {
+    // Resolve this tick's rays and move projectiles along, removing those that hit something or burnt out
+    pub fn step(&mut self, dt: f32, ships: &mut Vec<Ship>, pickups: &mut Pickups, ctx: &mut Context) {
+        self.rays.iter_mut().for_each(|ray| ray.intersect(ships, pickups, ctx));
+
+        let mut i = 0;
+
+        while i < self.projectiles.len() {
+            if self.projectiles[i].step(dt, ships, pickups, ctx) {
+                i += 1;
+            } else {
+                self.projectiles.swap_remove(i);
+            }
         }
     }
+
+    pub fn draw(&self, ctx: &mut Context, alpha: f32) {
+        self.rays.iter().for_each(|ray| ray.draw(ctx));
+        self.projectiles.iter().for_each(|projectile| projectile.draw(ctx, alpha));
+    }
+}
+
+// Find the ship a ray hits first, returning its index, the point hit and the time of impact along the ray
+pub fn cast(ray: &Ray<Point2<f32>>, ships: &[Ship]) -> Option<(usize, Point2<f32>, f32)> {
+    ships.iter().enumerate()
+        // Filter map to ships that the ray intersects
+        .filter_map(|(i, ship)| {
+            let rigid_body = ship.handle.borrow();
+            rigid_body.shape().as_ref().toi_and_normal_with_ray(&rigid_body.position(), ray, true)
+                .map(|intersection| (i, ray.origin + ray.dir * intersection.toi, intersection.toi))
+        })
+        // Find the intersection closest to the origin of the ray
+        .ord_subset_min_by_key(|&(_, _, toi)| toi)
+}
+
+// Damage a ship at a point, removing it if it has been destroyed and adding any pieces that were cut off as new ships
+pub fn hit(ships: &mut Vec<Ship>, i: usize, point: Point2<f32>, damage: &Damage, pickups: &mut Pickups, ctx: &mut Context) {
+    let mut debris = Vec::new();
+
+    if ships[i].damage(point, damage, ctx, &mut debris, pickups) {
+        ships.remove(i);
+    }
+
+    ships.extend(debris);
 }
 
 #[derive(Clone)]
 pub struct Weapon {
     tag: WeaponType,
+    group: usize,
     rotation: f32,
+    // Builds up with the power the weapon receives and is spent each time it fires
+    charge: f32,
+    // Ticks until the weapon can fire again
+    cooldown: u32,
+    heat: f32,
+    // Set when the weapon hits full heat, and cleared once it has cooled down
+    overheated: bool,
     ray: Option<WeaponRay>
 }
 
@@ -44,30 +349,125 @@ impl Weapon {
     pub fn new(tag: WeaponType) -> Self {
         Self {
             tag,
+            group: tag.default_group(),
             rotation: 0.0,
+            charge: 0.0,
+            cooldown: 0,
+            heat: 0.0,
+            overheated: false,
             ray: None
         }
     }
 
-    pub fn step(&mut self, base: Vector2<f32>, rotation: f32, controls: &Controls, rays: &mut Vec<WeaponRay>) {
-        let target = (controls.mouse.1 - base.y).atan2(controls.mouse.0 - base.x) - rotation;
+    pub fn tag(&self) -> WeaponType {
+        self.tag
+    }
 
-        // Get the turn rate of the weapon
-        let turn = (target - self.rotation).signum() * 0.1;
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
 
-        if turn != 0.0 {
-            // If the rotation would bring it past the target, set it to the target
-            if (self.rotation + turn > target) != (self.rotation > target) {
-                self.rotation = target;
-            } else {
-                // Else rotate it
-                self.rotation += turn;
-            }
+    // Put the turret back how it was when a game was saved
+    pub fn restore(&mut self, rotation: f32, charge: f32, cooldown: u32, heat: f32, overheated: bool) {
+        self.rotation = rotation;
+        self.charge = charge;
+        self.cooldown = cooldown;
+        self.heat = heat;
+        self.overheated = overheated;
+    }
+
+    pub fn group(&self) -> usize {
+        self.group
+    }
+
+    pub fn charge(&self) -> f32 {
+        self.charge
+    }
+
+    pub fn cooldown(&self) -> u32 {
+        self.cooldown
+    }
+
+    pub fn heat(&self) -> f32 {
+        self.heat
+    }
+
+    pub fn overheated(&self) -> bool {
+        self.overheated
+    }
+
+    // Turn towards the group's aim and fire if the group is firing, taking rounds from ammo and returning the recoil impulse of anything fired
+    pub fn step(&mut self, mount: &Mount, input: &Input, ammo: &mut u32, shots: &mut Shots) -> Option<Vector2<f32>> {
+        let base = mount.position;
+
+        // A direction is the same for every weapon wherever it sits, where a point has to be looked at from the weapon
+        let (aim_x, aim_y) = input.aim_direction.unwrap_or_else(|| {
+            let (x, y) = input.aims[self.group];
+            (x - base.x, y - base.y)
+        });
+
+        let target = aim_y.atan2(aim_x) - mount.rotation;
+
+        // Work relative to the way the hardpoint faces, so the turret stays within its arc and never turns the long way round
+        let offset = wrap_angle(self.rotation - mount.facing).max(-ARC).min(ARC);
+        let target = wrap_angle(target - mount.facing).max(-ARC).min(ARC);
+
+        // Get the turn rate of the weapon, which is slower when it's damaged
+        let turn = (target - offset).signum() * TURN_RATE * mount.condition;
+
+        // If the rotation would bring it past the target, set it to the target
+        let offset = if (offset + turn > target) != (offset > target) {
+            target
+        } else {
+            offset + turn
+        };
+
+        self.rotation = mount.facing + offset;
+
+        let model = self.tag.firing_model();
+
+        // Weapons short on power take longer than a tick to charge up
+        self.charge = (self.charge + mount.power).min(1.0);
+        self.cooldown = self.cooldown.saturating_sub(1);
+        self.heat = (self.heat - model.cooling).max(0.0);
+
+        if self.overheated && self.heat <= OVERHEAT_RESET {
+            self.overheated = false;
+        }
+
+        let loaded = !model.uses_ammo || *ammo > 0;
+
+        if !input.fire[self.group] || self.charge < 1.0 || self.cooldown > 0 || self.overheated || !loaded {
+            return None;
         }
 
-        // If the mouse is down, add a ray
-        if controls.mouse_down {
-            rays.push(WeaponRay::new(self.tag, base, self.rotation + rotation));
+        self.charge -= 1.0;
+        self.cooldown = model.interval;
+        self.heat += model.heat_per_shot;
+
+        if self.heat >= 1.0 {
+            self.heat = 1.0;
+            self.overheated = true;
+        }
+
+        if model.uses_ammo {
+            *ammo -= 1;
+        }
+
+        let angle = self.rotation + mount.rotation;
+
+        match self.tag.ballistics() {
+            // Fire a projectile that carries on with the velocity of the hardpoint, and push the ship back
+            Some(ballistics) => {
+                let direction = Vector2::new(angle.cos(), angle.sin());
+                let velocity = mount.velocity + direction * ballistics.speed;
+                shots.projectiles.push(Projectile::new(self.tag, Point2::new(base.x, base.y), velocity, ballistics.lifetime));
+                Some(-direction * ballistics.speed * ballistics.mass)
+            },
+            None => {
+                shots.rays.push(WeaponRay::new(self.tag, base, angle));
+                None
+            }
         }
     }
 
@@ -96,39 +496,144 @@ impl WeaponRay {
         }
     }
 
-    pub fn draw(&self, ctx: &mut Context) {
-        ctx.set_colour(self.tag.ray_colour());
+    // A ray that has already been resolved somewhere else, running from one point to another
+    pub fn between(tag: WeaponType, start: Point2<f32>, end: Point2<f32>) -> Self {
+        let direction = end - start;
+        let length = direction.norm();
+
+        Self {
+            tag,
+            ray: Ray::new(start, if length > 0.0 { direction / length } else { Vector2::new(1.0, 0.0) }),
+            intersection: Some(end)
+        }
+    }
+
+    pub fn tag(&self) -> WeaponType {
+        self.tag
+    }
+
+    // Where the ray starts, and where it hit something or a point off into the distance
+    pub fn ends(&self) -> (Point2<f32>, Point2<f32>) {
         let origin = self.ray.origin;
-        // Get the end of the ray of a point off into the distance
-        let end = self.intersection.unwrap_or(origin + self.ray.dir * 2000.0);
+        (origin, self.intersection.unwrap_or(origin + self.ray.dir * 2000.0))
+    }
+
+    pub fn draw(&self, ctx: &mut Context) {
+        ctx.set_colour(self.tag.colour());
+        let (origin, end) = self.ends();
         ctx.draw_line(origin.x, origin.y, end.x, end.y);
     }
 
-    pub fn intersect(&mut self, ships: &mut Vec<Ship>, ctx: &mut Context) {
-        if let Some((i, intersection)) = ships.iter().enumerate()
-            // Filter map to rays that intersect
-            .filter_map(|(i, ship)| {
-                let rigid_body = ship.handle.borrow();
-                rigid_body.shape().as_ref().toi_and_normal_with_ray(&rigid_body.position(), &self.ray, true)
-            // Map to the index and intersection point
-            }.map(|intersection| (i, self.ray.origin + self.ray.dir * intersection.toi)))
-            // Find the intersection point closest to the origin of the ray
-            .ord_subset_min_by_key(|&(_, intersection)| distance(&self.ray.origin, &intersection)) {
-                // Set the intersection point for drawing
-                self.intersection = Some(intersection);
-
-                match self.tag {
-                    // Apply a force to the ship at the intersection point
-                    WeaponType::TractionBeam => {
-                        let mut rigid_body = ships[i].handle.borrow_mut();
-                        let relative = intersection.coords - rigid_body.position().translation.vector;
-                        rigid_body.apply_impulse_wrt_point(self.ray.dir.inverse() * 1000.0, relative);
-                    },
-                    // Damage the ship at the intersection point and remove the ship if it has been destroyed
-                    WeaponType::Laser => if ships[i].damage(intersection, ctx) {
-                        ships.remove(i);
-                    }
-                };
-        }
-    }
-}
\ No newline at end of file
+    pub fn intersect(&mut self, ships: &mut Vec<Ship>, pickups: &mut Pickups, ctx: &mut Context) {
+        let ship = cast(&self.ray, ships);
+
+        // Only the traction beam catches ore, and only when there isn't a ship in front of it
+        if let WeaponType::TractionBeam = self.tag {
+            if let Some((i, intersection, toi)) = pickups.cast(&self.ray) {
+                if ship.map_or(true, |(_, _, ship_toi)| toi < ship_toi) {
+                    self.intersection = Some(intersection);
+                    let mut rigid_body = pickups.pickups[i].handle.borrow_mut();
+                    let mass = 1.0 / rigid_body.inv_mass();
+                    rigid_body.apply_central_impulse(self.ray.dir.inverse() * ORE_PULL * mass);
+                    return;
+                }
+            }
+        }
+
+        if let Some((i, intersection, _)) = ship {
+            // Set the intersection point for drawing
+            self.intersection = Some(intersection);
+
+            match self.tag {
+                // Apply a force to the ship at the intersection point, which is taken relative to the centre of mass
+                WeaponType::TractionBeam => {
+                    let mut rigid_body = ships[i].handle.borrow_mut();
+                    let relative = intersection.coords - rigid_body.center_of_mass().coords;
+                    rigid_body.apply_impulse_wrt_point(self.ray.dir.inverse() * 1000.0, relative);
+                },
+                // Damage the ship at the intersection point
+                _ => {
+                    let damage = self.tag.damage_profile().at(distance(&self.ray.origin, &intersection));
+                    hit(ships, i, intersection, &damage, pickups, ctx);
+                }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damage_falls_off_over_its_range() {
+        let profile = WeaponType::Cannon.damage_profile();
+
+        assert!((profile.at(0.0).amount - profile.damage()).abs() < 1e-6);
+        assert!((profile.at(2750.0).amount - profile.damage() / 2.0).abs() < 1e-4);
+        assert_eq!(profile.at(5000.0).amount, 0.0);
+    }
+
+    #[test]
+    fn missiles_hit_as_hard_at_any_range() {
+        let profile = WeaponType::Missile.damage_profile();
+        assert!((profile.at(1_000_000.0).amount - profile.damage()).abs() < 1e-6);
+    }
+
+    // Fire a weapon along the x axis from a mount facing that way, returning the recoil, what was fired and the ammo left
+    fn fire(tag: WeaponType, velocity: Vector2<f32>, mut ammo: u32) -> (Option<Vector2<f32>>, Shots, u32) {
+        let mut weapon = Weapon::new(tag);
+        let mut input = Input::default();
+        input.fire[weapon.group()] = true;
+        input.aim_direction = Some((1.0, 0.0));
+
+        let mount = Mount {
+            position: Vector2::new(100.0, 50.0),
+            rotation: 0.0,
+            facing: 0.0,
+            velocity,
+            power: 1.0,
+            condition: 1.0
+        };
+
+        let mut shots = Shots::default();
+        let recoil = weapon.step(&mount, &input, &mut ammo, &mut shots);
+        (recoil, shots, ammo)
+    }
+
+    #[test]
+    fn projectiles_carry_on_with_the_mount() {
+        let (_, shots, ammo) = fire(WeaponType::Cannon, Vector2::new(20.0, -30.0), 10);
+        let speed = WeaponType::Cannon.ballistics().unwrap().speed;
+
+        assert_eq!(shots.projectiles.len(), 1);
+        assert!((shots.projectiles[0].velocity() - Vector2::new(20.0 + speed, -30.0)).norm() < 1e-3);
+        assert_eq!(ammo, 9);
+    }
+
+    #[test]
+    fn projectiles_push_back_on_the_ship() {
+        let (recoil, _, _) = fire(WeaponType::Cannon, Vector2::new(20.0, -30.0), 10);
+        let ballistics = WeaponType::Cannon.ballistics().unwrap();
+
+        // Only the speed the shot was fired at counts, not what it inherited
+        assert!((recoil.unwrap() - Vector2::new(-ballistics.speed * ballistics.mass, 0.0)).norm() < 1e-3);
+    }
+
+    #[test]
+    fn rays_dont_recoil() {
+        let (recoil, shots, ammo) = fire(WeaponType::Laser, Vector2::new(20.0, -30.0), 10);
+
+        assert!(recoil.is_none());
+        assert_eq!(shots.rays.len(), 1);
+        assert_eq!(ammo, 10);
+    }
+
+    #[test]
+    fn nothing_fires_without_ammo() {
+        let (recoil, shots, _) = fire(WeaponType::Cannon, Vector2::new(0.0, 0.0), 0);
+
+        assert!(recoil.is_none());
+        assert!(shots.projectiles.is_empty());
+    }
+}